@@ -1,7 +1,9 @@
 // Lowercase item types a through z have priorities 1 through 26.
 // Uppercase item types A through Z have priorities 27 through 52.
 
+#[cfg(test)]
 use std::collections::HashSet;
+use std::fmt;
 
 #[allow(dead_code)]
 static INPUT: &'static str = include_str!("./day03.txt");
@@ -19,6 +21,15 @@ impl Element {
         }
     }
 
+    #[allow(dead_code)]
+    fn from_priority(priority: u64) -> Self {
+        match priority {
+            1..=26 => Self((b'a' + priority as u8 - 1) as char),
+            27..=52 => Self((b'A' + priority as u8 - 27) as char),
+            _ => panic!("no item has priority {priority}"),
+        }
+    }
+
     #[allow(dead_code)]
     fn priority(&self) -> u64 {
         if self.0.is_ascii_lowercase() {
@@ -31,6 +42,77 @@ impl Element {
     }
 }
 
+/// A set of item types packed into the low 52 bits of a `u64`, where the
+/// item with priority `p` is bit `p - 1`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    #[allow(dead_code)]
+    const EMPTY: Self = Self(0);
+
+    #[allow(dead_code)]
+    fn insert(&mut self, element: &Element) {
+        self.0 |= 1 << (element.priority() - 1);
+    }
+
+    #[allow(dead_code)]
+    fn contains(&self, element: &Element) -> bool {
+        self.0 & (1 << (element.priority() - 1)) != 0
+    }
+
+    #[allow(dead_code)]
+    fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    #[allow(dead_code)]
+    fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    #[allow(dead_code)]
+    fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    #[allow(dead_code)]
+    fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Priorities of the items in the set, lowest first.
+    #[allow(dead_code)]
+    fn priorities(self) -> impl Iterator<Item = u64> {
+        let mut bits = self.0;
+
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let bit = bits.trailing_zeros();
+            // clear the lowest set bit
+            bits &= bits - 1;
+            Some(bit as u64 + 1)
+        })
+    }
+
+    #[allow(dead_code)]
+    fn items(self) -> impl Iterator<Item = Element> {
+        self.priorities().map(Element::from_priority)
+    }
+}
+
+impl<'a> FromIterator<&'a Element> for ItemSet {
+    fn from_iter<T: IntoIterator<Item = &'a Element>>(iter: T) -> Self {
+        let mut set = Self::EMPTY;
+        for element in iter {
+            set.insert(element);
+        }
+        set
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct RuckSack {
     #[allow(dead_code)]
@@ -40,7 +122,23 @@ struct RuckSack {
 }
 
 impl RuckSack {
+    /// Item types found in both compartments.
     #[allow(dead_code)]
+    fn common(&self) -> ItemSet {
+        self.compartment_1
+            .iter()
+            .collect::<ItemSet>()
+            .intersection(self.compartment_2.iter().collect())
+    }
+
+    /// Every item type in the rucksack, either compartment.
+    #[allow(dead_code)]
+    fn items(&self) -> ItemSet {
+        self.itering().collect()
+    }
+
+    /// Reference for `common`, kept for the tests to check it against.
+    #[cfg(test)]
     fn compare(&self) -> HashSet<Element> {
         self.compartment_1
             .iter()
@@ -77,13 +175,113 @@ fn parse_input(input: &str) -> Vec<RuckSack> {
         .collect()
 }
 
+/// A group whose rucksacks don't share exactly one item type. `group`
+/// counts from 0.
+#[derive(Debug, PartialEq)]
+struct BadgeProblem {
+    group: usize,
+    badges: ItemSet,
+}
+
+#[derive(Debug, PartialEq)]
+enum GroupError {
+    ZeroSize,
+    /// The rucksacks left over at the end don't fill a group.
+    IncompleteGroup {
+        group: usize,
+        found: usize,
+        expected: usize,
+    },
+    Badges(Vec<BadgeProblem>),
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroSize => write!(f, "groups need at least one rucksack"),
+            Self::IncompleteGroup {
+                group,
+                found,
+                expected,
+            } => write!(
+                f,
+                "group {group} has {found} rucksack(s), expected {expected}"
+            ),
+            Self::Badges(problems) => {
+                write!(f, "{} group(s) without a single badge", problems.len())?;
+                for problem in problems {
+                    let badges = problem
+                        .badges
+                        .items()
+                        .map(|item| item.0)
+                        .collect::<String>();
+                    match badges.len() {
+                        0 => write!(f, "\ngroup {}: no common item", problem.group)?,
+                        _ => write!(f, "\ngroup {}: common items {badges}", problem.group)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Splits the rucksacks into consecutive groups of `size`.
+#[allow(dead_code)]
+fn groups(rucksacks: &[RuckSack], size: usize) -> Result<Vec<&[RuckSack]>, GroupError> {
+    if size == 0 {
+        return Err(GroupError::ZeroSize);
+    }
+
+    let groups = rucksacks.chunks_exact(size);
+    if !groups.remainder().is_empty() {
+        return Err(GroupError::IncompleteGroup {
+            group: rucksacks.len() / size,
+            found: groups.remainder().len(),
+            expected: size,
+        });
+    }
+
+    Ok(groups.collect())
+}
+
+/// The one item type every rucksack of each group carries. Every group
+/// sharing none or several of them is reported, not just the first.
+#[allow(dead_code)]
+fn find_badges(rucksacks: &[RuckSack], size: usize) -> Result<Vec<Element>, GroupError> {
+    let mut badges = vec![];
+    let mut problems = vec![];
+
+    for (group, rucksacks) in groups(rucksacks, size)?.into_iter().enumerate() {
+        let common = rucksacks
+            .iter()
+            .map(RuckSack::items)
+            .reduce(ItemSet::intersection)
+            .unwrap_or_default();
+
+        match common.len() {
+            1 => badges.extend(common.items()),
+            _ => problems.push(BadgeProblem {
+                group,
+                badges: common,
+            }),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(badges)
+    } else {
+        Err(GroupError::Badges(problems))
+    }
+}
+
 #[test]
 fn part1() {
     let a = parse_input(INPUT);
 
     let b = a
         .iter()
-        .map(|rsck| rsck.compare().iter().map(|elm| elm.priority()).sum::<u64>())
+        .map(|rsck| rsck.common().priorities().sum::<u64>())
         .sum::<u64>();
 
     println!("{:?}", b);
@@ -93,26 +291,109 @@ fn part1() {
 fn part2() {
     let a = parse_input(INPUT);
 
-    let b = (0..a.len())
-        .step_by(3)
-        .map(|idx| &a[idx..idx + 3])
-        .filter_map(|rsck| {
-            rsck.iter().fold(None::<HashSet<_>>, |acc, abc| {
-                acc.map_or_else(
-                    || Some(abc.itering().collect()),
-                    |acc| {
-                        Some(
-                            acc.intersection(&abc.itering().collect())
-                                .copied()
-                                .collect(),
-                        )
-                    },
-                )
-            })
-        })
-        .flat_map(|intersection| intersection.into_iter())
-        .map(|item| item.priority())
+    let b = find_badges(&a, 3)
+        .unwrap()
+        .iter()
+        .map(Element::priority)
         .sum::<u64>();
 
     println!("{:?}", b);
 }
+
+#[test]
+fn badges() {
+    let a = parse_input(
+        "vJrwpWtwJgWrhcsFMMfFFhFp
+jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
+PmmdzqPrVvPwwTWBwg
+wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
+ttgJtRGJQctTZtZT
+CrZsJsPPZsGzwwsLwLmpwMDw",
+    );
+
+    assert_eq!(find_badges(&a, 3), Ok(vec![Element('r'), Element('Z')]));
+    assert_eq!(
+        find_badges(&a, 6),
+        Err(GroupError::Badges(vec![BadgeProblem {
+            group: 0,
+            badges: ItemSet::EMPTY
+        }]))
+    );
+    assert_eq!(
+        find_badges(&a[..1], 1),
+        Err(GroupError::Badges(vec![BadgeProblem {
+            group: 0,
+            badges: a[0].items()
+        }]))
+    );
+
+    assert_eq!(groups(&a, 0), Err(GroupError::ZeroSize));
+    assert_eq!(
+        find_badges(&a[..5], 3),
+        Err(GroupError::IncompleteGroup {
+            group: 1,
+            found: 2,
+            expected: 3
+        })
+    );
+    assert_eq!(groups(&a, 2).map(|groups| groups.len()), Ok(3));
+
+    let error = find_badges(&a, 2).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "3 group(s) without a single badge
+group 0: common items frsFM
+group 1: common items qvwBT
+group 2: common items GJZ"
+    );
+    assert!(find_badges(&a, 6)
+        .unwrap_err()
+        .to_string()
+        .ends_with("group 0: no common item"));
+}
+
+/// The bitset answers against the `HashSet` ones they replaced.
+#[test]
+fn item_sets_match_hash_sets() {
+    let a = parse_input(INPUT);
+
+    for rsck in a.iter() {
+        let expected = rsck.compare();
+
+        assert_eq!(rsck.common().len() as usize, expected.len());
+        assert!(rsck.common().items().all(|item| expected.contains(&item)));
+        assert_eq!(
+            rsck.items().items().collect::<HashSet<_>>(),
+            rsck.itering().cloned().collect::<HashSet<_>>()
+        );
+    }
+
+    for group in a.chunks_exact(3) {
+        let expected = group
+            .iter()
+            .map(|rsck| rsck.itering().cloned().collect::<HashSet<_>>())
+            .reduce(|acc, items| acc.intersection(&items).cloned().collect())
+            .unwrap();
+        let badges = group
+            .iter()
+            .map(RuckSack::items)
+            .reduce(ItemSet::intersection)
+            .unwrap();
+
+        assert_eq!(badges.items().collect::<HashSet<_>>(), expected);
+    }
+
+    let items = "aAzZ"
+        .chars()
+        .map(|chr| Element::from_char(chr).unwrap())
+        .collect::<Vec<_>>();
+    let set = items.iter().collect::<ItemSet>();
+    assert_eq!(set.priorities().collect::<Vec<_>>(), [1, 26, 27, 52]);
+    assert_eq!(
+        set.items().collect::<Vec<_>>(),
+        "azAZ".chars().map(Element).collect::<Vec<_>>()
+    );
+    assert!(set.contains(&items[3]) && !set.contains(&Element('b')));
+    assert_eq!(set.union(ItemSet::EMPTY), set);
+    assert!(set.intersection(ItemSet::EMPTY).is_empty());
+}
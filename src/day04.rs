@@ -1,56 +1,221 @@
-#[allow(unused_imports)]
-use std::collections::{HashSet, HashMap};
+use std::fmt;
+
+use crate::interval::{Interval, IntervalIndex, IntervalSet};
 
 #[allow(dead_code)]
 static INPUT: &'static str = include_str!("./day04.txt");
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Assignment {
-    #[allow(dead_code)]
-    section1: Vec<u64>,
-    #[allow(dead_code)]
-    section2: Vec<u64>
+    first: Interval,
+    second: Interval,
+}
+
+impl Assignment {
+    fn elves(&self) -> [Interval; 2] {
+        [self.first, self.second]
+    }
+}
+
+/// A line with any number of elves on it, from `parse_groups`.
+#[derive(Debug, Clone, PartialEq)]
+struct Group {
+    elves: Vec<Interval>,
 }
 
 #[allow(dead_code)]
-fn parse_input(input: &str) -> Vec<Assignment> {
-    input.lines()
-        .map(|chr| {
-            chr.split(",").collect::<Vec<_>>()
-        })
-        .map(|ghj| {
-
-            let a = ghj[0]
-                .split("-")
-                .map(|chr| {
-                    chr.parse::<u64>().ok().unwrap()
-                })
-                .collect::<Vec<_>>();
-
-            let b = ghj[1]
-                .split("-")
-                .map(|chr| {
-                    chr.parse::<u64>().ok().unwrap()
-                })
-                .collect::<Vec<_>>();
-
-            Assignment {
-                section1: a,
-                section2: b
+impl Group {
+    /// Whether some elf's sections are all covered by another elf.
+    fn has_contained(&self) -> bool {
+        self.pairs().any(|(a, b)| a.contains(b) || b.contains(a))
+    }
+
+    fn has_overlap(&self) -> bool {
+        self.pairs().any(|(a, b)| a.overlaps(b))
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = (&Interval, &Interval)> {
+        self.elves
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, a)| self.elves[idx + 1..].iter().map(move |b| (a, b)))
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, PartialEq)]
+enum AssignmentError {
+    ElfCount { line: usize, expected: usize, found: usize },
+    InvalidRange { line: usize, range: String },
+    InvalidNumber { line: usize, number: String },
+    ReversedRange { line: usize, start: u64, end: u64 },
+}
+
+impl fmt::Display for AssignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ElfCount { line, expected, found } => {
+                write!(f, "line {line}: expected {expected} elves, found {found}")
+            }
+            Self::InvalidRange { line, range } => {
+                write!(f, "line {line}: `{range}` is not a `start-end` range")
             }
-        }).collect()
+            Self::InvalidNumber { line, number } => {
+                write!(f, "line {line}: `{number}` is not a section number")
+            }
+            Self::ReversedRange { line, start, end } => {
+                write!(f, "line {line}: range {start}-{end} ends before it starts")
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn parse_range(line: usize, range: &str) -> Result<Interval, AssignmentError> {
+    let (start, end) = range.split_once('-').ok_or_else(|| AssignmentError::InvalidRange {
+        line,
+        range: range.to_string(),
+    })?;
+
+    let number = |number: &str| {
+        // `u64::from_str` would also take a leading `+`
+        if number.is_empty() || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(AssignmentError::InvalidNumber { line, number: number.to_string() });
+        }
+        number.parse::<u64>().map_err(|_| AssignmentError::InvalidNumber {
+            line,
+            number: number.to_string(),
+        })
+    };
+    let (start, end) = (number(start)?, number(end)?);
+
+    Interval::new(start, end).ok_or(AssignmentError::ReversedRange { line, start, end })
+}
+
+/// One group per non-empty line, each with exactly `elves` ranges, or any
+/// number of them when `elves` is `None`.
+#[allow(dead_code)]
+fn parse_groups(input: &str, elves: Option<usize>) -> Result<Vec<Group>, AssignmentError> {
+    let mut groups = vec![];
+
+    for (idx, text) in input.lines().enumerate() {
+        let line = idx + 1;
+        let text = text.trim_end();
+        if text.is_empty() {
+            continue;
+        }
+
+        let ranges = text
+            .split(',')
+            .map(|range| parse_range(line, range))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(expected) = elves.filter(|expected| *expected != ranges.len()) {
+            return Err(AssignmentError::ElfCount {
+                line,
+                expected,
+                found: ranges.len(),
+            });
+        }
+
+        groups.push(Group { elves: ranges });
+    }
+
+    Ok(groups)
+}
+
+#[allow(dead_code)]
+fn parse_input(input: &str) -> Result<Vec<Assignment>, AssignmentError> {
+    Ok(parse_groups(input, Some(2))?
+        .into_iter()
+        .map(|group| Assignment {
+            first: group.elves[0],
+            second: group.elves[1],
+        })
+        .collect())
+}
+
+/// Every elf's sections from a list of assignments or groups, for
+/// questions about the whole camp rather than one line. Entries carry the
+/// line's position and the elf's position within it.
+#[allow(dead_code)]
+struct SectionIndex {
+    index: IntervalIndex<(usize, usize)>,
+}
+
+#[allow(dead_code)]
+impl SectionIndex {
+    fn new(assignments: &[Assignment]) -> Self {
+        Self::build(assignments.iter().map(|assignment| assignment.elves().to_vec()))
+    }
+
+    fn from_groups(groups: &[Group]) -> Self {
+        Self::build(groups.iter().map(|group| group.elves.clone()))
+    }
+
+    fn build(lines: impl Iterator<Item = Vec<Interval>>) -> Self {
+        let entries = lines
+            .enumerate()
+            .flat_map(|(idx, elves)| {
+                elves
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(elf, sections)| (sections, (idx, elf)))
+            })
+            .collect();
+
+        Self {
+            index: IntervalIndex::new(entries),
+        }
+    }
+
+    /// Sections in `within` that no elf has to clean.
+    fn uncovered(&self, within: &Interval) -> IntervalSet {
+        self.index
+            .depths()
+            .into_iter()
+            .map(|(sections, _)| sections)
+            .collect::<IntervalSet>()
+            .gaps(within)
+    }
+
+    /// The largest number of elves assigned to one section, and the
+    /// sections that many elves share. `None` without any assignments.
+    fn most_covered(&self) -> Option<(usize, IntervalSet)> {
+        let depths = self.index.depths();
+        let most = depths.iter().map(|(_, depth)| *depth).max()?;
+
+        let sections = depths
+            .into_iter()
+            .filter(|(_, depth)| *depth == most)
+            .map(|(sections, _)| sections)
+            .collect();
+
+        Some((most, sections))
+    }
+
+    /// Positions of the assignments where at least one elf works somewhere
+    /// in `sections`, in order.
+    fn overlapping(&self, sections: &Interval) -> Vec<usize> {
+        let mut result = self
+            .index
+            .overlapping(sections)
+            .into_iter()
+            .map(|(_, (idx, _))| *idx)
+            .collect::<Vec<_>>();
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
 }
 
 #[test]
 fn part1() {
-    let a = parse_input(INPUT);
+    let a = parse_input(INPUT).unwrap();
 
     let b = a.iter().filter(|stc| {
 
-        let range1 = (stc.section1[0]..=stc.section1[1]).collect::<HashSet<_>>();
-        let range2 = (stc.section2[0]..=stc.section2[1]).collect::<HashSet<_>>();
-
-        range1.is_subset(&range2) || range1.is_superset(&range2)
+        stc.first.contains(&stc.second) || stc.second.contains(&stc.first)
     })
     .collect::<Vec<_>>();
 
@@ -59,16 +224,187 @@ fn part1() {
 
 #[test]
 fn part2() {
-    let a = parse_input(INPUT);
+    let a = parse_input(INPUT).unwrap();
 
     let b = a.iter().filter(|elem| {
-        
-        let range1 = (elem.section1[0]..=elem.section1[1]).collect::<HashSet<_>>();
-        let range2 = (elem.section2[0]..=elem.section2[1]).collect::<HashSet<_>>();
 
-        !range1.is_disjoint(&range2)
+        elem.first.overlaps(&elem.second)
     })
     .collect::<Vec<_>>();
 
     println!("{}", b.len());
-}
\ No newline at end of file
+}
+
+#[test]
+fn large_ranges() {
+    let a =
+        parse_input("0-18446744073709551615,5-18446744073709551614\n3-7,8-18446744073709551615")
+            .unwrap();
+
+    assert!(a[0].first.contains(&a[0].second));
+    assert!(!a[1].first.overlaps(&a[1].second));
+    assert_eq!(
+        a[1].first.union(&a[1].second).map(|range| range.len()),
+        Some((1 << 64) - 3)
+    );
+}
+
+#[test]
+fn section_index() {
+    let a = parse_input("2-4,6-8\n2-3,4-5\n5-7,7-9\n2-8,3-7\n6-6,4-6\n2-6,4-8").unwrap();
+    let index = SectionIndex::new(&a);
+
+    let sections = |set: IntervalSet| {
+        set.iter()
+            .map(|range| range.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        sections(index.uncovered(&Interval::new(1, 10).unwrap())),
+        ["1-1", "10-10"]
+    );
+    assert_eq!(
+        index
+            .most_covered()
+            .map(|(most, set)| (most, sections(set))),
+        Some((8, vec!["6-6".to_string()]))
+    );
+    assert_eq!(index.overlapping(&Interval::new(9, 12).unwrap()), [2]);
+    assert_eq!(
+        index.overlapping(&Interval::new(1, 1).unwrap()),
+        Vec::<usize>::new()
+    );
+    assert_eq!(
+        index.overlapping(&Interval::new(3, 3).unwrap()),
+        [0, 1, 3, 5]
+    );
+
+    assert_eq!(SectionIndex::new(&[]).most_covered(), None);
+
+    let a = parse_input(INPUT).unwrap();
+    let index = SectionIndex::new(&a);
+    for section in 0..=100 {
+        let expected = a
+            .iter()
+            .enumerate()
+            .filter(|(_, elem)| {
+                elem.first.contains_point(section) || elem.second.contains_point(section)
+            })
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+
+        assert_eq!(index.overlapping(&Interval::point(section)), expected);
+    }
+}
+
+#[test]
+fn parse_errors() {
+    let errors = [
+        (
+            "1-2,3-4\n5-6",
+            AssignmentError::ElfCount {
+                line: 2,
+                expected: 2,
+                found: 1,
+            },
+        ),
+        (
+            "1-2,3-4,5-6",
+            AssignmentError::ElfCount {
+                line: 1,
+                expected: 2,
+                found: 3,
+            },
+        ),
+        (
+            "\n1-2,3",
+            AssignmentError::InvalidRange {
+                line: 2,
+                range: "3".to_string(),
+            },
+        ),
+        (
+            "1-2,3-x",
+            AssignmentError::InvalidNumber {
+                line: 1,
+                number: "x".to_string(),
+            },
+        ),
+        (
+            "1-2,-4",
+            AssignmentError::InvalidNumber {
+                line: 1,
+                number: "".to_string(),
+            },
+        ),
+        (
+            "+1-2,3-4",
+            AssignmentError::InvalidNumber {
+                line: 1,
+                number: "+1".to_string(),
+            },
+        ),
+        (
+            "1-2,3-4-5",
+            AssignmentError::InvalidNumber {
+                line: 1,
+                number: "4-5".to_string(),
+            },
+        ),
+        (
+            "1-2,3-18446744073709551616",
+            AssignmentError::InvalidNumber {
+                line: 1,
+                number: "18446744073709551616".to_string(),
+            },
+        ),
+        (
+            "1-2,3-4\n\n7-3,1-1",
+            AssignmentError::ReversedRange {
+                line: 3,
+                start: 7,
+                end: 3,
+            },
+        ),
+    ];
+    for (input, error) in errors {
+        assert_eq!(parse_input(input), Err(error));
+    }
+
+    assert_eq!(
+        parse_input("1-2,3-4\n5-6").unwrap_err().to_string(),
+        "line 2: expected 2 elves, found 1"
+    );
+    assert_eq!(parse_input("2-4,6-8\r\n\n").map(|a| a.len()), Ok(1));
+}
+
+#[test]
+fn groups() {
+    let groups = parse_groups("1-9,2-3,8-8\n1-2,5-6\n4-4\n1-3,4-5,5-9,10-10", None).unwrap();
+
+    assert_eq!(
+        groups
+            .iter()
+            .map(|group| group.elves.len())
+            .collect::<Vec<_>>(),
+        [3, 2, 1, 4]
+    );
+    assert_eq!(
+        groups.iter().map(Group::has_contained).collect::<Vec<_>>(),
+        [true, false, false, false]
+    );
+    assert_eq!(
+        groups.iter().map(Group::has_overlap).collect::<Vec<_>>(),
+        [true, false, false, true]
+    );
+
+    let index = SectionIndex::from_groups(&groups);
+    assert_eq!(index.overlapping(&Interval::point(5)), [0, 1, 3]);
+    assert_eq!(index.most_covered().map(|(most, _)| most), Some(4));
+
+    assert_eq!(
+        parse_groups("1-2,3-4,5-6", Some(3)).map(|groups| groups.len()),
+        Ok(1)
+    );
+}
@@ -1,46 +1,168 @@
 #[allow(unused_imports)]
 use std::collections::{HashSet, VecDeque};
+use std::io::{self, Read};
+
+#[allow(unused_imports)]
+use crate::iter::windows_of;
 
 #[allow(dead_code)]
 static INPUT: &'static str = include_str!("./day06.txt");
 
-struct WindowIterator<I: Iterator> {
-    n: usize,
-    iter: I,
-    buffer: VecDeque<I::Item>,
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum MarkerKind {
+    StartOfPacket,
+    StartOfMessage,
+}
+
+impl MarkerKind {
+    #[allow(dead_code)]
+    fn window_size(&self) -> usize {
+        match self {
+            Self::StartOfPacket => 4,
+            Self::StartOfMessage => 14,
+        }
+    }
+}
+
+/// `offset` is the number of bytes consumed when the marker completes,
+/// which is exactly what the puzzle asks for.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Marker {
+    kind: MarkerKind,
+    offset: usize,
+}
+
+/// Keeps the last `n` bytes in a ring plus a count per byte value, so
+/// checking "are they all different" is O(1) per byte.
+struct DistinctWindow {
+    kind: MarkerKind,
+    ring: VecDeque<u8>,
+    counts: [u16; 256],
+    duplicates: usize,
+    found: bool,
 }
 
-impl<I: Iterator> WindowIterator<I> {
+impl DistinctWindow {
     #[allow(dead_code)]
-    fn new(n: usize, iter: I) -> Self {
+    fn new(kind: MarkerKind) -> Self {
         Self {
-            n,
-            iter,
-            buffer: VecDeque::with_capacity(n),
+            kind,
+            ring: VecDeque::with_capacity(kind.window_size()),
+            counts: [0; 256],
+            duplicates: 0,
+            found: false,
+        }
+    }
+
+    #[allow(dead_code)]
+    fn push(&mut self, byte: u8) {
+        if self.ring.len() == self.kind.window_size() {
+            if let Some(old) = self.ring.pop_front() {
+                self.counts[old as usize] -= 1;
+                if self.counts[old as usize] == 1 {
+                    self.duplicates -= 1;
+                }
+            }
+        }
+
+        self.ring.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 2 {
+            self.duplicates += 1;
         }
     }
+
+    #[allow(dead_code)]
+    fn is_distinct(&self) -> bool {
+        self.ring.len() == self.kind.window_size() && self.duplicates == 0
+    }
 }
 
-impl<I> Iterator for WindowIterator<I>
-where
-    I: Iterator,
-    I::Item: Clone,
-{
-    type Item = VecDeque<I::Item>;
+/// Reads the datastream in fixed size chunks and yields every marker kind
+/// the first time it shows up, so memory stays the same no matter how long
+/// the stream is.
+struct MarkerDetector<R: Read> {
+    reader: R,
+    chunk: Box<[u8]>,
+    filled: usize,
+    cursor: usize,
+    offset: usize,
+    windows: Vec<DistinctWindow>,
+}
+
+impl<R: Read> MarkerDetector<R> {
+    #[allow(dead_code)]
+    fn new(reader: R) -> Self {
+        Self::with_chunk_size(reader, 8 * 1024)
+    }
+
+    #[allow(dead_code)]
+    fn with_chunk_size(reader: R, chunk_size: usize) -> Self {
+        Self {
+            reader,
+            chunk: vec![0; chunk_size.max(1)].into_boxed_slice(),
+            filled: 0,
+            cursor: 0,
+            offset: 0,
+            windows: vec![
+                DistinctWindow::new(MarkerKind::StartOfPacket),
+                DistinctWindow::new(MarkerKind::StartOfMessage),
+            ],
+        }
+    }
+
+    fn next_byte(&mut self) -> Option<io::Result<u8>> {
+        while self.cursor == self.filled {
+            match self.reader.read(&mut self.chunk) {
+                Ok(0) => return None,
+                Ok(n) => {
+                    self.filled = n;
+                    self.cursor = 0;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        let byte = self.chunk[self.cursor];
+        self.cursor += 1;
+        Some(Ok(byte))
+    }
+}
+
+impl<R: Read> Iterator for MarkerDetector<R> {
+    type Item = io::Result<Marker>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.buffer.len() < self.n {
-            self.buffer
-                .extend((&mut self.iter).take(self.n - self.buffer.len()));
-
-            Some(self.buffer.clone())
-        } else if let Some(item) = self.iter.next() {
-            self.buffer.pop_front();
-            self.buffer.push_back(item);
-
-            Some(self.buffer.clone())
-        } else {
-            None
+        loop {
+            // both windows can complete on the same byte, so the second one
+            // is picked up on the following call before reading any further
+            if let Some(window) = self
+                .windows
+                .iter_mut()
+                .find(|window| !window.found && window.is_distinct())
+            {
+                window.found = true;
+                return Some(Ok(Marker {
+                    kind: window.kind,
+                    offset: self.offset,
+                }));
+            }
+
+            if self.windows.iter().all(|window| window.found) {
+                return None;
+            }
+
+            let byte = match self.next_byte()? {
+                Ok(byte) => byte,
+                Err(err) => return Some(Err(err)),
+            };
+            self.offset += 1;
+
+            self.windows
+                .iter_mut()
+                .filter(|window| !window.found)
+                .for_each(|window| window.push(byte));
         }
     }
 }
@@ -49,10 +171,8 @@ where
 fn part1() {
     let chars = INPUT.chars();
 
-    let mut window = WindowIterator::new(4, chars);
-
-    let result = window
-        .position(|elem| elem.into_iter().collect::<HashSet<_>>().len() == 4)
+    let result = windows_of(4, chars)
+        .position(|elem| elem.iter().collect::<HashSet<_>>().len() == 4)
         .map(|i| i + 4);
 
     println!("{}", result.unwrap());
@@ -62,11 +182,98 @@ fn part1() {
 fn part2() {
     let chars = INPUT.chars();
 
-    let mut window = WindowIterator::new(14, chars);
-
-    let result = window
-        .position(|elem| elem.into_iter().collect::<HashSet<_>>().len() == 14)
+    let result = windows_of(14, chars)
+        .position(|elem| elem.iter().collect::<HashSet<_>>().len() == 14)
         .map(|i| i + 14);
 
     println!("{}", result.unwrap());
 }
+
+#[test]
+fn streaming() {
+    let markers = MarkerDetector::new(INPUT.as_bytes())
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+
+    for marker in markers.iter() {
+        let n = marker.kind.window_size();
+        let expected = windows_of(n, INPUT.chars())
+            .position(|elem| elem.iter().collect::<HashSet<_>>().len() == n)
+            .map(|i| i + n);
+
+        assert_eq!(Some(marker.offset), expected);
+    }
+
+    assert_eq!(markers.len(), 2);
+}
+
+#[test]
+fn streaming_examples() {
+    let cases = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+        ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+        ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+        ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+    ];
+
+    for (input, packet, message) in cases {
+        // a tiny chunk size forces windows to straddle chunk boundaries
+        let markers = MarkerDetector::with_chunk_size(input.as_bytes(), 3)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            markers,
+            vec![
+                Marker {
+                    kind: MarkerKind::StartOfPacket,
+                    offset: packet
+                },
+                Marker {
+                    kind: MarkerKind::StartOfMessage,
+                    offset: message
+                },
+            ]
+        );
+    }
+}
+
+#[test]
+fn streaming_long_input() {
+    // a generated stream that only becomes distinct at the very end,
+    // read lazily so it is never held in memory as a whole
+    struct Generated {
+        remaining: usize,
+        tail: &'static [u8],
+    }
+
+    impl Read for Generated {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.remaining > 0 {
+                let n = buf.len().min(self.remaining);
+                buf[..n].fill(b'a');
+                self.remaining -= n;
+                Ok(n)
+            } else {
+                let n = buf.len().min(self.tail.len());
+                buf[..n].copy_from_slice(&self.tail[..n]);
+                self.tail = &self.tail[n..];
+                Ok(n)
+            }
+        }
+    }
+
+    let filler = 1 << 20;
+    let reader = Generated {
+        remaining: filler,
+        tail: b"bcdefghijklmno",
+    };
+
+    let markers = MarkerDetector::new(reader)
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+
+    assert_eq!(markers[0].offset, filler + 3);
+    assert_eq!(markers[1].offset, filler + 13);
+}
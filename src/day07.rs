@@ -1,4 +1,14 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    fs, io,
+    path::Path,
+};
+
+#[cfg(test)]
+use crate::rng::Lcg;
 
 #[allow(dead_code)]
 static INPUT: &'static str = include_str!("./day07.txt");
@@ -75,113 +85,562 @@ impl Tree<Directory<'_>> {
                     + file_size
             })
     }
+
+    /// Absolute path of `id`, with `root_id` being `/`.
+    fn path(&self, root_id: NodeId, id: NodeId) -> String {
+        let mut names = vec![];
+        let mut current = Some(id);
+
+        while let Some(id) = current.filter(|id| *id != root_id) {
+            names.extend(self.get(id).map(|dir| dir.name.as_ref()));
+            current = self.get_parent(id);
+        }
+
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    fn find_child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.iter_children(id)
+            .find(|sub_id| self.get(*sub_id).is_some_and(|dir| dir.name == name))
+    }
 }
 
 #[derive(Debug)]
 struct File<'a> {
-    name: &'a str,
+    name: Cow<'a, str>,
     size: u64,
 }
 
 impl<'a> File<'a> {
-    fn new(name: &'a str, size: u64) -> Self {
-        Self { name, size }
+    fn new(name: impl Into<Cow<'a, str>>, size: u64) -> Self {
+        Self {
+            name: name.into(),
+            size,
+        }
     }
 }
 
 #[derive(Debug)]
 struct Directory<'a> {
-    name: &'a str,
+    name: Cow<'a, str>,
     files: Vec<File<'a>>,
 }
 
 impl<'a> Directory<'a> {
-    fn new(name: &'a str) -> Self {
+    fn new(name: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            name,
+            name: name.into(),
             files: vec![],
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum ParserError {
-    UnknownCommand,
-    DirectoryNotFound,
-    InvalidEntry,
+    UnknownCommand { line: usize },
+    DirectoryNotFound { line: usize },
+    InvalidEntry { line: usize },
+    UnexpectedOutput { line: usize },
+    ConflictingEntry { line: usize, name: String },
 }
 
-fn parse_input(input: &str) -> Result<(Tree<Directory<'_>>, NodeId), ParserError> {
-    let mut result = Tree::new();
-    let root_id = result.push(None, Directory::new("")).unwrap();
-    let mut current_directory = root_id;
-
-    for line in input.lines() {
-        if line.is_empty() {
-            // ignore
-            todo!()
-        } else if line.starts_with("$ ") {
-            // parse command
-            let mut tokens = line[2..].split_ascii_whitespace();
-            let cmd = tokens.next();
-            let arg1 = tokens.next();
-
-            if let (Some(cmd), Some(arg1)) = (cmd, arg1) {
-                if cmd == "cd" {
-                    if arg1 == "/" {
-                        current_directory = root_id;
-                    } else if arg1 == ".." {
-                        current_directory = result
-                            .get_parent(current_directory)
-                            .ok_or(ParserError::DirectoryNotFound)?;
-                    } else {
-                        current_directory = result
-                            .iter_children(current_directory)
-                            .filter_map(|id| result.get(id).map(|dir| (id, dir)))
-                            .find(|(_, dir)| dir.name == arg1)
-                            .map(|(id, _)| id)
-                            .ok_or(ParserError::DirectoryNotFound)?;
-                    }
-                } else {
-                    return Err(ParserError::UnknownCommand);
+/// Replays a terminal session against a `Tree<Directory>`.
+///
+/// Directories are created the first time they're seen, either from an `ls`
+/// listing or from a `cd` into them, and listing the same directory again
+/// only adds what is new, so nothing gets counted twice.
+struct Session<'a> {
+    fs: Tree<Directory<'a>>,
+    root_id: NodeId,
+    current_directory: NodeId,
+    listing: bool,
+}
+
+impl<'a> Session<'a> {
+    fn new() -> Self {
+        let mut fs = Tree::new();
+        let root_id = fs.push(None, Directory::new("")).unwrap();
+
+        Self {
+            fs,
+            root_id,
+            current_directory: root_id,
+            listing: false,
+        }
+    }
+
+    fn execute(&mut self, line_no: usize, line: &'a str) -> Result<(), ParserError> {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+
+        if let Some(command) = line.strip_prefix("$ ") {
+            self.listing = false;
+
+            // like `dir` entries, the argument is the rest of the line, so
+            // names with spaces in them can be reached
+            let (cmd, arg) = match command.split_once(' ') {
+                Some((cmd, arg)) => (cmd, Some(arg).filter(|arg| !arg.trim().is_empty())),
+                None => (command, None),
+            };
+
+            match (cmd, arg) {
+                ("cd", Some(path)) => self.change_directory(line_no, path),
+                ("cd", None) => Err(ParserError::InvalidEntry { line: line_no }),
+                ("ls", None) => {
+                    self.listing = true;
+                    Ok(())
                 }
+                ("ls", Some(_)) => Err(ParserError::InvalidEntry { line: line_no }),
+                _ => Err(ParserError::UnknownCommand { line: line_no }),
             }
-        } else if line.starts_with("dir ") {
-            // add directory
-            let name = &line[4..];
-            let directory = Directory::new(name);
+        } else if !self.listing {
+            Err(ParserError::UnexpectedOutput { line: line_no })
+        } else if let Some(name) = line.strip_prefix("dir ") {
+            self.add_directory(line_no, self.current_directory, name)
+                .map(|_| ())
+        } else {
+            let entry = line
+                .split_once(' ')
+                .and_then(|(size, name)| Some((size.parse::<u64>().ok()?, name)))
+                .filter(|(_, name)| !name.trim().is_empty());
+
+            match entry {
+                Some((size, name)) => self.add_file(line_no, File::new(name, size)),
+                None => Err(ParserError::InvalidEntry { line: line_no }),
+            }
+        }
+    }
 
-            result.push(Some(current_directory), directory);
+    fn change_directory(&mut self, line_no: usize, path: &'a str) -> Result<(), ParserError> {
+        let mut directory = if path.starts_with('/') {
+            self.root_id
         } else {
-            // add file
-            let mut tokens = line.split_ascii_whitespace();
-            let size = tokens.next();
-            let name = tokens.next();
-
-            if let (Some(size), Some(name)) = (size.and_then(|x| x.parse::<u64>().ok()), name) {
-                let file = File::new(name, size);
-
-                if let Some(directory) = result.get_mut(current_directory) {
-                    directory.files.push(file)
-                } else {
-                    return Err(ParserError::DirectoryNotFound);
+            self.current_directory
+        };
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            directory = match name {
+                "." => directory,
+                // like a real shell, going up from the root stays at the root
+                ".." => self.fs.get_parent(directory).unwrap_or(self.root_id),
+                _ => self.add_directory(line_no, directory, name)?,
+            };
+        }
+
+        self.current_directory = directory;
+
+        Ok(())
+    }
+
+    fn add_directory(
+        &mut self,
+        line_no: usize,
+        parent: NodeId,
+        name: &'a str,
+    ) -> Result<NodeId, ParserError> {
+        if name.trim().is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(ParserError::InvalidEntry { line: line_no });
+        }
+
+        let is_file = self
+            .fs
+            .get(parent)
+            .ok_or(ParserError::DirectoryNotFound { line: line_no })?
+            .files
+            .iter()
+            .any(|file| file.name == name);
+
+        if is_file {
+            return Err(ParserError::ConflictingEntry {
+                line: line_no,
+                name: name.to_string(),
+            });
+        }
+
+        match self.fs.find_child(parent, name) {
+            Some(id) => Ok(id),
+            None => self
+                .fs
+                .push(Some(parent), Directory::new(name))
+                .ok_or(ParserError::DirectoryNotFound { line: line_no }),
+        }
+    }
+
+    fn add_file(&mut self, line_no: usize, file: File<'a>) -> Result<(), ParserError> {
+        let conflict = || ParserError::ConflictingEntry {
+            line: line_no,
+            name: file.name.to_string(),
+        };
+
+        if file.name.contains('/') {
+            return Err(ParserError::InvalidEntry { line: line_no });
+        }
+
+        if self
+            .fs
+            .find_child(self.current_directory, &file.name)
+            .is_some()
+        {
+            return Err(conflict());
+        }
+
+        let directory = self
+            .fs
+            .get_mut(self.current_directory)
+            .ok_or(ParserError::DirectoryNotFound { line: line_no })?;
+
+        match directory.files.iter().find(|f| f.name == file.name) {
+            Some(existing) if existing.size == file.size => Ok(()),
+            Some(_) => Err(conflict()),
+            None => {
+                directory.files.push(file);
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> (Tree<Directory<'a>>, NodeId) {
+        (self.fs, self.root_id)
+    }
+}
+
+fn parse_input(input: &str) -> Result<(Tree<Directory<'_>>, NodeId), ParserError> {
+    let mut session = Session::new();
+
+    for (idx, line) in input.lines().enumerate() {
+        session.execute(idx + 1, line)?;
+    }
+
+    Ok(session.finish())
+}
+
+/// Size reports over a parsed filesystem.
+///
+/// The total size of every directory is worked out once up front, since
+/// `get_total_size` walks the whole subtree again on each call.
+struct Report<'t, 'a> {
+    fs: &'t Tree<Directory<'a>>,
+    root_id: NodeId,
+    total_sizes: Vec<u64>,
+}
+
+impl<'t, 'a> Report<'t, 'a> {
+    fn new(fs: &'t Tree<Directory<'a>>, root_id: NodeId) -> Self {
+        let mut total_sizes = fs
+            .nodes
+            .iter()
+            .map(|dir| dir.files.iter().map(|file| file.size).sum::<u64>())
+            .collect::<Vec<_>>();
+
+        // a directory is always pushed before its children, so going from
+        // the last node to the first adds every subtree into its parent
+        // after the subtree itself is complete
+        for id in fs.iter_nodes().collect::<Vec<_>>().into_iter().rev() {
+            if let Some(parent) = fs.get_parent(id) {
+                total_sizes[parent.0] += total_sizes[id.0];
+            }
+        }
+
+        Self {
+            fs,
+            root_id,
+            total_sizes,
+        }
+    }
+
+    fn total_size(&self, id: NodeId) -> Option<u64> {
+        self.total_sizes.get(id.0).copied()
+    }
+
+    fn path(&self, id: NodeId) -> String {
+        self.fs.path(self.root_id, id)
+    }
+
+    /// The `n` largest directories, biggest first, like `du | sort -rh | head`.
+    fn largest(&self, n: usize) -> Vec<(NodeId, u64)> {
+        let mut result = self
+            .fs
+            .iter_nodes()
+            .zip(self.total_sizes.iter().copied())
+            .collect::<Vec<_>>();
+
+        result.sort_by_cached_key(|(id, size)| (Reverse(*size), self.path(*id)));
+        result.truncate(n);
+
+        result
+    }
+
+    /// The smallest directory that frees at least `required_space` once
+    /// deleted, on a disk of `disk_size`. `None` when even deleting
+    /// everything isn't enough.
+    fn smallest_to_free(&self, disk_size: u64, required_space: u64) -> Option<(NodeId, u64)> {
+        let used_size = self.total_size(self.root_id)?;
+        let free_size = disk_size.saturating_sub(used_size);
+        let size_to_delete = required_space.saturating_sub(free_size);
+
+        self.fs
+            .iter_nodes()
+            .zip(self.total_sizes.iter().copied())
+            .filter(|(_, size)| *size >= size_to_delete)
+            .min_by_key(|(_, size)| *size)
+    }
+
+    fn render_du(&self, n: usize) -> String {
+        self.largest(n)
+            .into_iter()
+            .map(|(id, size)| format!("{}\t{}\n", human_size(size), self.path(id)))
+            .collect()
+    }
+
+    fn render_tree(&self) -> String {
+        let mut result = String::new();
+        let _ = writeln!(
+            result,
+            "/ ({})",
+            human_size(self.total_size(self.root_id).unwrap_or(0))
+        );
+        self.render_subtree(self.root_id, "", &mut result);
+
+        result
+    }
+
+    fn render_subtree(&self, id: NodeId, prefix: &str, result: &mut String) {
+        enum Entry<'e> {
+            Dir(NodeId, &'e str),
+            File(&'e str, u64),
+        }
+
+        let mut entries = self
+            .fs
+            .iter_children(id)
+            .filter_map(|sub_id| {
+                self.fs
+                    .get(sub_id)
+                    .map(|dir| Entry::Dir(sub_id, dir.name.as_ref()))
+            })
+            .chain(
+                self.fs
+                    .get(id)
+                    .into_iter()
+                    .flat_map(|dir| dir.files.iter())
+                    .map(|file| Entry::File(file.name.as_ref(), file.size)),
+            )
+            .collect::<Vec<_>>();
+
+        entries.sort_by_key(|entry| match entry {
+            Entry::Dir(_, name) | Entry::File(name, _) => *name,
+        });
+
+        for (idx, entry) in entries.iter().enumerate() {
+            let last = idx + 1 == entries.len();
+            let branch = if last { "└── " } else { "├── " };
+
+            match entry {
+                Entry::Dir(sub_id, name) => {
+                    let size = self.total_size(*sub_id).unwrap_or(0);
+                    let _ = writeln!(result, "{prefix}{branch}{name}/ ({})", human_size(size));
+
+                    let indent = if last { "    " } else { "│   " };
+                    self.render_subtree(*sub_id, &format!("{prefix}{indent}"), result);
+                }
+                Entry::File(name, size) => {
+                    let _ = writeln!(result, "{prefix}{branch}{name} ({})", human_size(*size));
                 }
-            } else {
-                return Err(ParserError::InvalidEntry);
             }
         }
     }
+}
+
+/// Formats a size the way `du -h` does: powers of 1024, one decimal below
+/// 10, always rounded up.
+fn human_size(size: u64) -> String {
+    let units = ["K", "M", "G", "T", "P", "E"];
 
-    Ok((result, root_id))
+    if size < 1024 {
+        return size.to_string();
+    }
+
+    let size = size as u128;
+    let mut divisor = 1024u128;
+    for unit in units {
+        // pick the unit by what gets printed, so 1023.1K shows as 1.0M
+        let tenths = (size * 10).div_ceil(divisor);
+        if tenths < 100 {
+            return format!("{}.{}{unit}", tenths / 10, tenths % 10);
+        }
+
+        let whole = size.div_ceil(divisor);
+        if whole < 1024 || unit == "E" {
+            return format!("{whole}{unit}");
+        }
+        divisor *= 1024;
+    }
+
+    unreachable!("every size fits in exabytes")
+}
+
+fn check_entry_name(name: &str) -> io::Result<()> {
+    // a transcript has to be able to spell the name on one line
+    if name.trim().is_empty() || name.contains(['/', '\n', '\r']) || name == "." || name == ".." {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{name:?} can't be used as a file name"),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+impl Tree<Directory<'_>> {
+    /// Writes the subtree under `id` into the existing directory `path`.
+    /// Files are created with `set_len`, so they are sparse on filesystems
+    /// that support it and take (almost) no real space.
+    fn materialise(&self, id: NodeId, path: &Path) -> io::Result<()> {
+        let directory = self
+            .get(id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unknown directory"))?;
+
+        for file in directory.files.iter() {
+            check_entry_name(&file.name)?;
+            fs::File::create(path.join(file.name.as_ref()))?.set_len(file.size)?;
+        }
+
+        for sub_id in self.iter_children(id) {
+            if let Some(sub_directory) = self.get(sub_id) {
+                check_entry_name(&sub_directory.name)?;
+
+                let sub_path = path.join(sub_directory.name.as_ref());
+                fs::create_dir(&sub_path)?;
+                self.materialise(sub_id, &sub_path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Tree<Directory<'static>> {
+    /// Builds a tree by walking a real directory. Symlinks are skipped so a
+    /// link back up the hierarchy can't send the walk into a loop.
+    fn scan(path: &Path) -> io::Result<(Self, NodeId)> {
+        let mut result = Tree::new();
+        let root_id = result.push(None, Directory::new("")).unwrap();
+
+        result.scan_into(root_id, path)?;
+
+        Ok((result, root_id))
+    }
+
+    fn scan_into(&mut self, id: NodeId, path: &Path) -> io::Result<()> {
+        let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let name = entry.file_name().into_string().map_err(|name| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{name:?} is not valid UTF-8"),
+                )
+            })?;
+            let metadata = fs::symlink_metadata(entry.path())?;
+
+            if metadata.is_dir() {
+                let sub_id = self.push(Some(id), Directory::new(name)).unwrap();
+                self.scan_into(sub_id, &entry.path())?;
+            } else if metadata.is_file() {
+                if let Some(directory) = self.get_mut(id) {
+                    directory.files.push(File::new(name, metadata.len()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Traversal {
+    /// `cd` into each directory and back out with `cd ..`, like the puzzle
+    /// input does.
+    DepthFirst,
+    /// Visit the tree level by level, jumping around with absolute paths.
+    BreadthFirst,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TranscriptOptions {
+    traversal: Traversal,
+    /// Adds a pointless `cd ..` followed by a `cd` straight back after each
+    /// listing, to exercise the parser's navigation.
+    redundant_moves: bool,
+}
+
+impl Tree<Directory<'_>> {
+    /// Writes a terminal session that `parse_input` turns back into this tree.
+    fn transcript(&self, root_id: NodeId, options: TranscriptOptions) -> String {
+        let mut result = String::from("$ cd /\n");
+
+        match options.traversal {
+            Traversal::DepthFirst => self.transcript_depth_first(root_id, options, &mut result),
+            Traversal::BreadthFirst => {
+                let mut queue = VecDeque::from([root_id]);
+
+                while let Some(id) = queue.pop_front() {
+                    if id != root_id {
+                        let _ = writeln!(result, "$ cd {}", self.path(root_id, id));
+                    }
+                    self.transcript_listing(id, options, &mut result);
+                    queue.extend(self.iter_children(id));
+                }
+            }
+        }
+
+        result
+    }
+
+    fn transcript_depth_first(&self, id: NodeId, options: TranscriptOptions, result: &mut String) {
+        self.transcript_listing(id, options, result);
+
+        for sub_id in self.iter_children(id) {
+            if let Some(sub_directory) = self.get(sub_id) {
+                let _ = writeln!(result, "$ cd {}", sub_directory.name);
+                self.transcript_depth_first(sub_id, options, result);
+                result.push_str("$ cd ..\n");
+            }
+        }
+    }
+
+    fn transcript_listing(&self, id: NodeId, options: TranscriptOptions, result: &mut String) {
+        let Some(directory) = self.get(id) else {
+            return;
+        };
+
+        result.push_str("$ ls\n");
+        for sub_id in self.iter_children(id) {
+            if let Some(sub_directory) = self.get(sub_id) {
+                let _ = writeln!(result, "dir {}", sub_directory.name);
+            }
+        }
+        for file in directory.files.iter() {
+            let _ = writeln!(result, "{} {}", file.size, file.name);
+        }
+
+        if options.redundant_moves && self.get_parent(id).is_some() {
+            let _ = writeln!(result, "$ cd ..\n$ cd {}", directory.name);
+        }
+    }
 }
 
 #[test]
 fn part1() -> Result<(), ParserError> {
-    let (fs, _) = parse_input(INPUT)?;
+    let (fs, root_id) = parse_input(INPUT)?;
+    let report = Report::new(&fs, root_id);
 
     let result = fs
         .iter_nodes()
-        .filter_map(|id| fs.get_total_size(id))
+        .filter_map(|id| report.total_size(id))
         .filter(|size| *size <= 100000)
         .sum::<u64>();
 
@@ -196,18 +655,238 @@ fn part2() -> Result<(), ParserError> {
     let total_size = 70_000_000;
     let required_space = 30_000_000;
 
-    let used_size = fs.get_total_size(root_id).unwrap();
-    let free_size = total_size - used_size;
-    let size_to_delete = required_space - free_size;
-
-    let result = fs
-        .iter_nodes()
-        .filter_map(|id| fs.get_total_size(id))
-        .filter(|size| *size >= size_to_delete)
-        .min()
+    let (_, result) = Report::new(&fs, root_id)
+        .smallest_to_free(total_size, required_space)
         .unwrap();
 
     println!("{}", result);
 
     Ok(())
 }
+
+#[test]
+fn session() -> Result<(), ParserError> {
+    let input = "$ cd /a/b\n\
+                 $ ls\n\
+                 10 x\n\
+                 \n\
+                 $ cd ../../c\n\
+                 $ ls\n\
+                 20 y\n\
+                 $ cd /\n\
+                 $ ls\n\
+                 dir a\n\
+                 dir c\n\
+                 $ ls\n\
+                 dir a\n\
+                 5 z\n\
+                 5 z\n\
+                 $ cd ..\n\
+                 $ cd a/./b\n\
+                 $ ls\n\
+                 10 x";
+
+    let (fs, root_id) = parse_input(input)?;
+
+    // root, a, b and c, with every directory and file stored only once
+    assert_eq!(fs.iter_nodes().count(), 4);
+    assert_eq!(fs.iter_children(root_id).count(), 2);
+    assert_eq!(fs.get_total_size(root_id), Some(35));
+
+    Ok(())
+}
+
+#[test]
+fn session_errors() {
+    let cases = [
+        (
+            "$ ls\n10 x\n$ ls\n11 x",
+            ParserError::ConflictingEntry {
+                line: 4,
+                name: "x".to_string(),
+            },
+        ),
+        (
+            "$ ls\ndir x\n10 x",
+            ParserError::ConflictingEntry {
+                line: 3,
+                name: "x".to_string(),
+            },
+        ),
+        ("$ cd /\n10 x", ParserError::UnexpectedOutput { line: 2 }),
+        ("$ rm x", ParserError::UnknownCommand { line: 1 }),
+        ("$ ls\nten x", ParserError::InvalidEntry { line: 2 }),
+        ("$ cd", ParserError::InvalidEntry { line: 1 }),
+        ("$ ls\n10 ", ParserError::InvalidEntry { line: 2 }),
+        ("$ ls -l", ParserError::InvalidEntry { line: 1 }),
+    ];
+
+    for (input, expected) in cases {
+        assert_eq!(parse_input(input).err(), Some(expected));
+    }
+}
+
+#[test]
+fn names_with_spaces() -> Result<(), ParserError> {
+    let input = "$ ls\n\
+                 dir my dir\n\
+                 $ cd my dir\n\
+                 $ ls\n\
+                 10 my file\n\
+                 $ cd /my dir/\n\
+                 $ ls\n\
+                 10 my file";
+
+    let (fs, root_id) = parse_input(input)?;
+    let report = Report::new(&fs, root_id);
+    assert_eq!(
+        report.render_tree().lines().collect::<Vec<_>>(),
+        ["/ (10)", "└── my dir/ (10)", "    └── my file (10)"]
+    );
+
+    let options = TranscriptOptions {
+        traversal: Traversal::DepthFirst,
+        redundant_moves: true,
+    };
+    let transcript = fs.transcript(root_id, options);
+    let (parsed, parsed_root_id) = parse_input(&transcript)?;
+    assert_eq!(
+        Report::new(&parsed, parsed_root_id).render_tree(),
+        report.render_tree()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn report() -> Result<(), ParserError> {
+    let input = "$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n\
+                 $ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n\
+                 $ cd e\n$ ls\n584 i\n$ cd ..\n$ cd ..\n\
+                 $ cd d\n$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k";
+
+    let (fs, root_id) = parse_input(input)?;
+    let report = Report::new(&fs, root_id);
+
+    for id in fs.iter_nodes() {
+        assert_eq!(report.total_size(id), fs.get_total_size(id));
+    }
+
+    assert_eq!(report.render_du(3), "47M\t/\n24M\t/d\n93K\t/a\n");
+
+    let expected = [
+        "/ (47M)",
+        "├── a/ (93K)",
+        "│   ├── e/ (584)",
+        "│   │   └── i (584)",
+        "│   ├── f (29K)",
+        "│   ├── g (2.5K)",
+        "│   └── h.lst (62K)",
+        "├── b.txt (15M)",
+        "├── c.dat (8.2M)",
+        "└── d/ (24M)",
+        "    ├── d.ext (5.4M)",
+        "    ├── d.log (7.7M)",
+        "    ├── j (3.9M)",
+        "    └── k (6.9M)",
+    ];
+    assert_eq!(report.render_tree().lines().collect::<Vec<_>>(), expected);
+
+    let (id, size) = report.smallest_to_free(70_000_000, 30_000_000).unwrap();
+    assert_eq!(report.path(id), "/d");
+    assert_eq!(size, 24933642);
+
+    // rounded up, with the unit following the rounded value, like `du -h`
+    assert_eq!(human_size(1023), "1023");
+    assert_eq!(human_size(1023 * 1024 + 1), "1.0M");
+    assert_eq!(human_size(10 * 1024 - 10), "10K");
+    assert_eq!(human_size(1025), "1.1K");
+    assert_eq!(human_size(29116), "29K");
+    assert_eq!(human_size(1536), "1.5K");
+
+    Ok(())
+}
+
+#[test]
+fn disk_round_trip() -> io::Result<()> {
+    // removes the directory again even when an assertion fails
+    struct TempDir(std::path::PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    let dir = TempDir(std::env::temp_dir().join(format!("aoc-day07-{}", std::process::id())));
+    fs::create_dir(&dir.0)?;
+
+    let (fs, root_id) = parse_input(INPUT).unwrap();
+    fs.materialise(root_id, &dir.0)?;
+
+    let (scanned, scanned_root_id) = Tree::scan(&dir.0)?;
+
+    assert_eq!(scanned.iter_nodes().count(), fs.iter_nodes().count());
+    assert_eq!(
+        scanned.get_total_size(scanned_root_id),
+        fs.get_total_size(root_id)
+    );
+
+    let expected = Report::new(&fs, root_id).smallest_to_free(70_000_000, 30_000_000);
+    let actual = Report::new(&scanned, scanned_root_id).smallest_to_free(70_000_000, 30_000_000);
+    assert_eq!(actual.map(|(_, size)| size), expected.map(|(_, size)| size));
+
+    Ok(())
+}
+
+#[test]
+fn transcript_round_trip() -> Result<(), ParserError> {
+    // a large made up tree, grown with a small LCG so it's the same every run
+    let names = (0..50).map(|i| format!("n{i}")).collect::<Vec<_>>();
+    let mut rng = Lcg::new(12345);
+
+    let mut fs = Tree::new();
+    let root_id = fs.push(None, Directory::new("")).unwrap();
+    let mut directories = vec![root_id];
+
+    for i in 0..2000 {
+        let parent = directories[rng.index(directories.len())];
+        let name = format!("{}{i}", names[rng.index(names.len())]);
+
+        if rng.below(3) == 0 {
+            directories.push(fs.push(Some(parent), Directory::new(name)).unwrap());
+        } else if let Some(directory) = fs.get_mut(parent) {
+            directory.files.push(File::new(name, rng.below(1_000_000)));
+        }
+    }
+
+    let expected = Report::new(&fs, root_id).render_tree();
+
+    for traversal in [Traversal::DepthFirst, Traversal::BreadthFirst] {
+        for redundant_moves in [false, true] {
+            let options = TranscriptOptions {
+                traversal,
+                redundant_moves,
+            };
+            let transcript = fs.transcript(root_id, options);
+            let (parsed, parsed_root_id) = parse_input(&transcript)?;
+
+            assert_eq!(Report::new(&parsed, parsed_root_id).render_tree(), expected);
+        }
+    }
+
+    // the puzzle input survives the trip as well
+    let (fs, root_id) = parse_input(INPUT)?;
+    let options = TranscriptOptions {
+        traversal: Traversal::DepthFirst,
+        redundant_moves: false,
+    };
+    let transcript = fs.transcript(root_id, options);
+    let (parsed, parsed_root_id) = parse_input(&transcript)?;
+    assert_eq!(
+        Report::new(&parsed, parsed_root_id).render_tree(),
+        Report::new(&fs, root_id).render_tree()
+    );
+
+    Ok(())
+}
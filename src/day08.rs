@@ -1,14 +1,28 @@
-use std::{collections::HashMap, convert::Infallible, str::FromStr};
+#[cfg(test)]
+use std::{collections::HashMap, convert::Infallible};
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use crate::image;
+#[cfg(test)]
+use crate::rng::Lcg;
 
 static INPUT: &'static str = include_str!("./day08.txt");
 
 type Position = (usize, usize);
 
+/// The original `HashMap` version, kept for the tests to check `Grid`
+/// against.
+#[cfg(test)]
 #[derive(Debug)]
 struct Forest {
     trees: HashMap<Position, u8>,
 }
 
+#[cfg(test)]
 impl FromStr for Forest {
     type Err = Infallible;
 
@@ -30,6 +44,7 @@ impl FromStr for Forest {
     }
 }
 
+#[cfg(test)]
 impl Forest {
     fn is_visible(&self, (x, y): Position) -> bool {
         let height = self.trees.get(&(x, y)).copied().unwrap_or(0);
@@ -93,14 +108,355 @@ impl Forest {
     }
 }
 
+/// A dense, row-major grid, used both for tree heights and for the
+/// per-tree results computed from them.
+#[derive(Debug, PartialEq, Clone)]
+struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+#[derive(Debug, PartialEq)]
+enum GridError {
+    RaggedRow { line: usize },
+    InvalidHeight { line: usize, column: usize },
+}
+
+impl FromStr for Grid<u8> {
+    type Err = GridError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut width = None;
+        let mut height = 0;
+        let mut cells = vec![];
+
+        for (y, line) in s.lines().enumerate() {
+            for (x, chr) in line.chars().enumerate() {
+                let tree = chr.to_digit(10).ok_or(GridError::InvalidHeight {
+                    line: y + 1,
+                    column: x + 1,
+                })?;
+                cells.push(tree as u8);
+            }
+
+            if *width.get_or_insert(line.chars().count()) != line.chars().count() {
+                return Err(GridError::RaggedRow { line: y + 1 });
+            }
+            height += 1;
+        }
+
+        Ok(Grid {
+            width: width.unwrap_or(0),
+            height,
+            cells,
+        })
+    }
+}
+
+impl<T> Grid<T> {
+    fn get(&self, (x, y): Position) -> Option<&T> {
+        if x < self.width && y < self.height {
+            self.cells.get(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Position, &T)> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(idx, cell)| ((idx % self.width, idx / self.width), cell))
+    }
+
+    fn map_cells<U>(&self, cells: Vec<U>) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells,
+        }
+    }
+
+    /// Every row and column, once in each direction, as cell indices
+    /// ordered from the edge the trees are looked at from.
+    fn lines(&self) -> impl Iterator<Item = Vec<usize>> + '_ {
+        let rows = (0..self.height).flat_map(move |y| {
+            let row = (0..self.width)
+                .map(|x| y * self.width + x)
+                .collect::<Vec<_>>();
+            let reversed = row.iter().rev().copied().collect::<Vec<_>>();
+            [row, reversed]
+        });
+
+        let columns = (0..self.width).flat_map(move |x| {
+            let column = (0..self.height)
+                .map(|y| y * self.width + x)
+                .collect::<Vec<_>>();
+            let reversed = column.iter().rev().copied().collect::<Vec<_>>();
+            [column, reversed]
+        });
+
+        rows.chain(columns)
+    }
+}
+
+impl Grid<u8> {
+    /// Whether each tree can be seen from outside the forest. Every line is
+    /// swept once while keeping the tallest tree so far, so this is linear
+    /// in the number of trees.
+    fn visibility(&self) -> Grid<bool> {
+        let mut visible = vec![false; self.cells.len()];
+
+        for line in self.lines() {
+            let mut tallest = None;
+
+            for idx in line {
+                let height = self.cells[idx];
+
+                if tallest.is_none_or(|tallest| height > tallest) {
+                    visible[idx] = true;
+                    tallest = Some(height);
+                }
+            }
+        }
+
+        self.map_cells(visible)
+    }
+
+    /// The scenic score of each tree. Along each line a monotonic stack
+    /// holds the trees that can still block the view, so every tree is
+    /// pushed and popped at most once per direction.
+    fn scenic_scores(&self) -> Grid<usize> {
+        let mut scores = vec![1; self.cells.len()];
+        let mut stack: Vec<(usize, u8)> = Vec::with_capacity(self.width.max(self.height));
+
+        for line in self.lines() {
+            stack.clear();
+
+            for (distance_from_edge, idx) in line.into_iter().enumerate() {
+                let height = self.cells[idx];
+
+                while stack.last().is_some_and(|(_, h)| *h < height) {
+                    stack.pop();
+                }
+
+                // either the first tree at least as tall, or the edge
+                let viewing_distance = stack.last().map_or(distance_from_edge, |(blocker, _)| {
+                    distance_from_edge - blocker
+                });
+
+                scores[idx] *= viewing_distance;
+                stack.push((distance_from_edge, height));
+            }
+        }
+
+        self.map_cells(scores)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ImageFormat {
+    Pgm,
+    Ppm,
+}
+
+/// Black through red and yellow to white, so the best spots stand out.
+fn heat_color(intensity: u8) -> [u8; 3] {
+    let i = intensity as usize * 3;
+
+    [
+        i.min(255),
+        i.saturating_sub(255).min(255),
+        i.saturating_sub(510).min(255),
+    ]
+    .map(|c| c as u8)
+}
+
+impl Grid<u8> {
+    /// The forest as text, with visible trees showing their height and
+    /// hidden ones replaced by `.`.
+    fn render_visibility(&self) -> String {
+        let visibility = self.visibility();
+
+        self.cells
+            .chunks(self.width.max(1))
+            .zip(visibility.cells.chunks(self.width.max(1)))
+            .map(|(heights, visible)| {
+                heights
+                    .iter()
+                    .zip(visible)
+                    .map(|(height, visible)| {
+                        if *visible {
+                            char::from(b'0' + height)
+                        } else {
+                            '.'
+                        }
+                    })
+                    .chain(std::iter::once('\n'))
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    /// Scenic scores scaled to `0..=255`. The scale is logarithmic because
+    /// a handful of trees score orders of magnitude above the rest.
+    fn scenic_heatmap(&self) -> Grid<u8> {
+        let scores = self.scenic_scores();
+        let max = scores.cells.iter().copied().max().unwrap_or(0);
+        let max_log = ((max + 1) as f64).ln();
+
+        self.map_cells(
+            scores
+                .cells
+                .iter()
+                .map(|score| {
+                    if max == 0 {
+                        0
+                    } else {
+                        (((*score + 1) as f64).ln() / max_log * 255.0).round() as u8
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Writes the scenic heatmap as an image, each tree `scale` pixels wide.
+    fn write_scenic_heatmap(
+        &self,
+        format: ImageFormat,
+        scale: usize,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let heatmap = self.scenic_heatmap();
+        let pixels = image::upscale(self.width, &heatmap.cells, scale);
+        let (width, height) = (self.width * scale, self.height * scale);
+
+        match format {
+            ImageFormat::Pgm => image::write_pgm(out, width, height, 255, &pixels),
+            ImageFormat::Ppm => {
+                let pixels = pixels.into_iter().map(heat_color).collect::<Vec<_>>();
+                image::write_ppm(out, width, height, &pixels)
+            }
+        }
+    }
+}
+
+/// A direction to look in, as the smallest integer step that stays on the
+/// line, so a slope of 2/4 walks the same lattice points as 1/2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Slope {
+    dx: i64,
+    dy: i64,
+}
+
+impl Slope {
+    const NORTH: Self = Self { dx: 0, dy: -1 };
+    const NORTH_EAST: Self = Self { dx: 1, dy: -1 };
+    const EAST: Self = Self { dx: 1, dy: 0 };
+    const SOUTH_EAST: Self = Self { dx: 1, dy: 1 };
+    const SOUTH: Self = Self { dx: 0, dy: 1 };
+    const SOUTH_WEST: Self = Self { dx: -1, dy: 1 };
+    const WEST: Self = Self { dx: -1, dy: 0 };
+    const NORTH_WEST: Self = Self { dx: -1, dy: -1 };
+
+    fn new(dx: i64, dy: i64) -> Option<Self> {
+        fn gcd(a: i64, b: i64) -> i64 {
+            if b == 0 {
+                a.abs()
+            } else {
+                gcd(b, a % b)
+            }
+        }
+
+        let divisor = gcd(dx, dy);
+
+        (divisor != 0).then(|| Self {
+            dx: dx / divisor,
+            dy: dy / divisor,
+        })
+    }
+
+    fn cardinal() -> [Self; 4] {
+        [Self::NORTH, Self::EAST, Self::SOUTH, Self::WEST]
+    }
+
+    fn compass() -> [Self; 8] {
+        [
+            Self::NORTH,
+            Self::NORTH_EAST,
+            Self::EAST,
+            Self::SOUTH_EAST,
+            Self::SOUTH,
+            Self::SOUTH_WEST,
+            Self::WEST,
+            Self::NORTH_WEST,
+        ]
+    }
+}
+
+/// The steps `k >= 1` for which `start + k * step` lands in `0..len`.
+fn steps_inside(start: i64, step: i64, len: usize) -> Option<(i64, i64)> {
+    let last = len as i64 - 1;
+
+    let (first, final_) = match step.signum() {
+        0 if (0..=last).contains(&start) => (1, i64::MAX),
+        0 => return None,
+        1 => (-(start.div_euclid(step)), (last - start).div_euclid(step)),
+        _ => (-((last - start).div_euclid(-step)), start.div_euclid(-step)),
+    };
+
+    let first = first.max(1);
+    (len > 0 && first <= final_).then_some((first, final_))
+}
+
+impl Grid<u8> {
+    /// The trees seen when looking from `from` along `slope`, nearest first.
+    /// Like in the puzzle a tree is seen when it is taller than every tree
+    /// before it. `from` may be outside the forest, and the tree standing at
+    /// `from` itself is never counted.
+    fn line_of_sight(&self, (x, y): (i64, i64), slope: Slope) -> Vec<Position> {
+        let range_x = steps_inside(x, slope.dx, self.width);
+        let range_y = steps_inside(y, slope.dy, self.height);
+
+        let Some(((first_x, last_x), (first_y, last_y))) = range_x.zip(range_y) else {
+            return vec![];
+        };
+
+        let mut tallest = None;
+
+        (first_x.max(first_y)..=last_x.min(last_y))
+            .map(|k| ((x + k * slope.dx) as usize, (y + k * slope.dy) as usize))
+            .filter(|pos| {
+                let height = self.get(*pos).copied().unwrap_or(0);
+                let seen = tallest.is_none_or(|tallest| height > tallest);
+                tallest = tallest.max(Some(height));
+                seen
+            })
+            .collect()
+    }
+
+    /// Every tree seen from `from` along any of `slopes`.
+    fn visible_from(
+        &self,
+        from: (i64, i64),
+        slopes: impl IntoIterator<Item = Slope>,
+    ) -> HashSet<Position> {
+        slopes
+            .into_iter()
+            .flat_map(|slope| self.line_of_sight(from, slope))
+            .collect()
+    }
+}
+
 #[test]
 fn part1() {
-    let forest = INPUT.parse::<Forest>().unwrap();
+    let forest = INPUT.parse::<Grid<u8>>().unwrap();
 
     let result = forest
-        .trees
-        .keys()
-        .filter(|pos| forest.is_visible(**pos))
+        .visibility()
+        .iter()
+        .filter(|(_, visible)| **visible)
         .count();
 
     println!("{:?}", result);
@@ -108,14 +464,127 @@ fn part1() {
 
 #[test]
 fn part2() {
-    let forest = INPUT.parse::<Forest>().unwrap();
+    let forest = INPUT.parse::<Grid<u8>>().unwrap();
 
-    let result = forest
-        .trees
-        .keys()
-        .map(|pos| forest.scenic_score(*pos))
-        .max()
-        .unwrap();
+    let result = forest.scenic_scores().cells.into_iter().max().unwrap();
 
     println!("{}", result);
 }
+
+#[test]
+fn dense_grid_matches_reference() {
+    let mut rng = Lcg::new(42);
+
+    let mut inputs = vec![
+        INPUT.to_string(),
+        "5".to_string(),
+        "30373\n25512\n65332\n33549\n35390".to_string(),
+    ];
+    for _ in 0..50 {
+        let width = rng.below(12) + 1;
+        let height = rng.below(12) + 1;
+        let max_height = rng.below(10) + 1;
+
+        inputs.push(
+            (0..height)
+                .map(|_| {
+                    (0..width)
+                        .map(|_| char::from(b'0' + rng.below(max_height) as u8))
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+    }
+
+    for input in inputs {
+        let reference = input.parse::<Forest>().unwrap();
+        let grid = input.parse::<Grid<u8>>().unwrap();
+
+        let visibility = grid.visibility();
+        let scenic_scores = grid.scenic_scores();
+
+        for pos in reference.trees.keys() {
+            assert_eq!(visibility.get(*pos), Some(&reference.is_visible(*pos)));
+            assert_eq!(scenic_scores.get(*pos), Some(&reference.scenic_score(*pos)));
+        }
+        assert_eq!(grid.cells.len(), reference.trees.len());
+    }
+
+    assert_eq!(
+        "123\n45".parse::<Grid<u8>>(),
+        Err(GridError::RaggedRow { line: 2 })
+    );
+    assert_eq!(
+        "12\n4x".parse::<Grid<u8>>(),
+        Err(GridError::InvalidHeight { line: 2, column: 2 })
+    );
+}
+
+#[test]
+fn render() -> io::Result<()> {
+    let forest = "30373\n25512\n65332\n33549\n35390"
+        .parse::<Grid<u8>>()
+        .unwrap();
+
+    assert_eq!(
+        forest.render_visibility(),
+        "30373\n255.2\n65.32\n3.5.9\n35390\n"
+    );
+
+    let heatmap = forest.scenic_heatmap();
+    assert_eq!(heatmap.get((2, 3)), Some(&255));
+    assert_eq!(heatmap.get((0, 0)), Some(&0));
+
+    let mut pgm = vec![];
+    forest.write_scenic_heatmap(ImageFormat::Pgm, 2, &mut pgm)?;
+    assert!(pgm.starts_with(b"P5\n10 10\n255\n"));
+    assert_eq!(pgm.len(), b"P5\n10 10\n255\n".len() + 100);
+
+    let mut ppm = vec![];
+    forest.write_scenic_heatmap(ImageFormat::Ppm, 1, &mut ppm)?;
+    assert!(ppm.starts_with(b"P6\n5 5\n255\n"));
+    assert_eq!(ppm.len(), b"P6\n5 5\n255\n".len() + 75);
+
+    Ok(())
+}
+
+#[test]
+fn line_of_sight() {
+    let forest = "30373\n25512\n65332\n33549\n35390"
+        .parse::<Grid<u8>>()
+        .unwrap();
+    let (width, height) = (forest.width as i64, forest.height as i64);
+
+    // looking in from just outside every edge is the same as part 1
+    let from_edges = (0..height)
+        .flat_map(|y| [((-1, y), Slope::EAST), ((width, y), Slope::WEST)])
+        .chain((0..width).flat_map(|x| [((x, -1), Slope::SOUTH), ((x, height), Slope::NORTH)]))
+        .flat_map(|(from, slope)| forest.line_of_sight(from, slope))
+        .collect::<HashSet<_>>();
+    let visibility = forest.visibility();
+    let expected = visibility
+        .iter()
+        .filter(|(_, visible)| **visible)
+        .map(|(pos, _)| pos)
+        .collect::<HashSet<_>>();
+    assert_eq!(from_edges, expected);
+
+    // a tower on the middle tree
+    assert_eq!(forest.line_of_sight((2, 2), Slope::NORTH), vec![(2, 1)]);
+    assert_eq!(
+        forest.line_of_sight((2, 2), Slope::SOUTH_EAST),
+        vec![(3, 3)]
+    );
+    assert_eq!(forest.visible_from((2, 2), Slope::compass()).len(), 10);
+    assert_eq!(forest.visible_from((2, 2), Slope::cardinal()).len(), 5);
+
+    // steeper slopes skip the cells that aren't on the line
+    assert_eq!(Slope::new(2, 4), Slope::new(1, 2));
+    assert_eq!(Slope::new(0, 0), None);
+    assert_eq!(
+        forest.line_of_sight((-1, -2), Slope::new(1, 2).unwrap()),
+        vec![(0, 0), (1, 2)]
+    );
+    assert!(forest.line_of_sight((10, 10), Slope::SOUTH_EAST).is_empty());
+}
@@ -1,122 +1,1066 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use crate::image;
+#[cfg(test)]
+use crate::ocr;
+
 static INPUT: &'static str = include_str!("./day10.txt");
 
-#[derive(Debug, Copy, Clone)]
+/// What the device looks like during one cycle: `strength_during` is the
+/// X register while the cycle runs, `strength_after` once it's done.
+#[derive(Debug, Copy, Clone, PartialEq)]
 struct Signal {
     cycle: usize,
     strength_during: isize,
     strength_after: isize,
 }
 
-impl Signal {
-    fn execute_instruction(&mut self, instruction: &Instruction) -> Vec<Self> {
-        let mut vec_of_self = vec![];
+/// The device's screen. Each cycle draws one pixel, left to right and top
+/// to bottom, lit when the sprite (centred on X) covers the pixel's column.
+#[derive(Debug, Clone, PartialEq)]
+struct Crt {
+    width: usize,
+    height: usize,
+    sprite_width: usize,
+    pixels: Vec<bool>,
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Self::new(40, 6, 3)
+    }
+}
+
+impl Crt {
+    fn new(width: usize, height: usize, sprite_width: usize) -> Self {
+        Self {
+            width,
+            height,
+            sprite_width,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    /// Draws the pixel for `signal.cycle`. Cycles past the last pixel wrap
+    /// around to the top left again.
+    fn draw(&mut self, signal: &Signal) {
+        if self.pixels.is_empty() || signal.cycle == 0 {
+            return;
+        }
+
+        let position = (signal.cycle - 1) % self.pixels.len();
+        let column = (position % self.width) as isize;
+        let sprite_start = signal.strength_during - (self.sprite_width as isize - 1) / 2;
 
-        for i in 1..=instruction.step {
-            self.cycle += 1;
-            self.strength_during = self.strength_after;
+        self.pixels[position] =
+            (sprite_start..sprite_start + self.sprite_width as isize).contains(&column);
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        x < self.width
+            && self
+                .pixels
+                .get(y * self.width + x)
+                .copied()
+                .unwrap_or(false)
+    }
 
-            if i == 2 {
-                self.strength_after += instruction.adder;
+    fn rows(&self) -> impl Iterator<Item = &[bool]> + '_ {
+        self.pixels.chunks(self.width.max(1))
+    }
+
+    /// `#` for lit pixels and `.` for dark ones, one line per row.
+    fn to_ascii(&self) -> String {
+        self.rows()
+            .flat_map(|row| {
+                row.iter()
+                    .map(|lit| if *lit { '#' } else { '.' })
+                    .chain(std::iter::once('\n'))
+            })
+            .collect()
+    }
+
+    /// Half block characters, two rows per line, so pixels come out
+    /// roughly square in a terminal.
+    fn to_unicode(&self) -> String {
+        let mut result = String::new();
+
+        for y in (0..self.height).step_by(2) {
+            for x in 0..self.width {
+                result.push(match (self.pixel(x, y), self.pixel(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
             }
-            vec_of_self.push(*self);
+            result.push('\n');
         }
-        vec_of_self
+
+        result
     }
 
-    fn draw_pixel(&self) -> String {
-        let current_pixel = ((self.cycle - 1) % 40) as isize;
+    /// Lit pixels come out black, each one `scale` pixels wide.
+    fn write_pbm(&self, scale: usize, out: &mut impl Write) -> io::Result<()> {
+        let pixels = image::upscale(self.width, &self.pixels, scale);
 
-        let sprite = vec![
-            self.strength_during - 1,
-            self.strength_during,
-            self.strength_during + 1,
-        ];
+        image::write_pbm(out, self.width * scale, self.height * scale, &pixels)
+    }
+}
 
-        if self.cycle % 40 == 0 {
-            "\n".to_string()
-        } else if sprite.contains(&current_pixel) {
-            "#".to_string()
-        } else {
-            ".".to_string()
+/// Which instructions the parser accepts. `Standard` is the puzzle's device,
+/// `Extended` adds `subx`, jumps and the extra registers.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+enum Mode {
+    #[default]
+    Standard,
+    Extended,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Register {
+    X,
+    Y,
+    Z,
+    W,
+}
+
+impl Register {
+    const ALL: [Self; 4] = [Self::X, Self::Y, Self::Z, Self::W];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|register| register.name() == name)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::X => "x",
+            Self::Y => "y",
+            Self::Z => "z",
+            Self::W => "w",
         }
     }
 }
 
-#[derive(Debug)]
+/// Jump offsets are relative to the jumping instruction, so `jmp 0` spins
+/// in place and `jmp 1` does nothing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Op {
+    Noop,
+    Add(Register, isize),
+    Sub(Register, isize),
+    Jmp(isize),
+    /// Jumps when the register isn't zero.
+    Jnz(Register, isize),
+}
+
+impl Op {
+    fn cycles(&self) -> usize {
+        match self {
+            Self::Noop | Self::Jmp(_) => 1,
+            Self::Add(..) | Self::Sub(..) | Self::Jnz(..) => 2,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Noop => write!(f, "noop"),
+            Self::Add(register, value) => write!(f, "add{} {value}", register.name()),
+            Self::Sub(register, value) => write!(f, "sub{} {value}", register.name()),
+            Self::Jmp(offset) => write!(f, "jmp {offset}"),
+            Self::Jnz(register, offset) => write!(f, "jnz {} {offset}", register.name()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 struct Instruction {
     idx: usize,
-    step: usize,
-    adder: isize,
+    op: Op,
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    UnknownOpcode { line: usize, opcode: String },
+    MissingOperand { line: usize },
+    InvalidOperand { line: usize },
+    UnexpectedOperand { line: usize },
+    UnknownLabel { line: usize, label: String },
+    DuplicateLabel { line: usize, label: String },
+    UnterminatedMacro { line: usize },
+    MacroArguments { line: usize, expected: usize },
+    MacroTooDeep { line: usize },
+    ReservedMacroName { line: usize, name: String },
+}
+
+/// Parses one instruction. `label` turns a jump target that isn't a number
+/// into an offset, when the caller knows about labels.
+fn parse_instruction(
+    idx: usize,
+    line: &str,
+    mode: Mode,
+    label: impl Fn(&str) -> Option<isize>,
+) -> Result<Instruction, ParseError> {
+    let mut tokens = line.split_ascii_whitespace();
+    let opcode = tokens.next().unwrap_or_default();
+    let operands = tokens.collect::<Vec<_>>();
+
+    let unknown = || ParseError::UnknownOpcode {
+        line: idx,
+        opcode: opcode.to_string(),
+    };
+    let value = |token: &str| {
+        token
+            .parse::<isize>()
+            .map_err(|_| ParseError::InvalidOperand { line: idx })
+    };
+    let target = |token: &str| match token.parse::<isize>() {
+        Ok(offset) => Ok(offset),
+        Err(_) if token.chars().all(|chr| chr.is_alphanumeric() || chr == '_') => label(token)
+            .ok_or(ParseError::UnknownLabel {
+                line: idx,
+                label: token.to_string(),
+            }),
+        Err(_) => Err(ParseError::InvalidOperand { line: idx }),
+    };
+    let register =
+        |token: &str| Register::from_name(token).ok_or(ParseError::InvalidOperand { line: idx });
+
+    if mode == Mode::Standard && !matches!(opcode, "noop" | "addx") {
+        return Err(unknown());
+    }
+
+    let expected = match opcode {
+        "noop" => 0,
+        "jmp" => 1,
+        "jnz" => 2,
+        _ if opcode.starts_with("add") || opcode.starts_with("sub") => 1,
+        _ => return Err(unknown()),
+    };
+
+    if operands.len() < expected {
+        return Err(ParseError::MissingOperand { line: idx });
+    } else if operands.len() > expected {
+        return Err(ParseError::UnexpectedOperand { line: idx });
+    }
+
+    let op = match (opcode, operands.as_slice()) {
+        ("noop", []) => Op::Noop,
+        ("jmp", [offset]) => Op::Jmp(target(offset)?),
+        ("jnz", [name, offset]) => Op::Jnz(register(name)?, target(offset)?),
+        (_, [operand]) => {
+            let (name, make): (_, fn(Register, isize) -> Op) = match opcode.split_at(3) {
+                ("add", name) => (name, Op::Add),
+                (_, name) => (name, Op::Sub),
+            };
+            make(
+                Register::from_name(name).ok_or_else(unknown)?,
+                value(operand)?,
+            )
+        }
+        _ => return Err(unknown()),
+    };
+
+    Ok(Instruction { idx, op })
+}
+
+fn parse_input(input: &str) -> Result<Vec<Instruction>, ParseError> {
+    parse_program(input, Mode::Standard)
 }
 
-fn parse_input(input: &str) -> Vec<Instruction> {
+fn parse_program(input: &str, mode: Mode) -> Result<Vec<Instruction>, ParseError> {
     input
         .lines()
         .enumerate() // i used enumerate to help me debug some problem i got
+        .filter(|(_, line)| !line.trim().is_empty())
         .map(|(mut idx, line)| {
             idx += 1;
-            if line.starts_with("addx") {
-                let step = 2 as usize;
-                let adder = line.split_ascii_whitespace().nth(1);
-
-                Instruction {
-                    idx,
-                    step,
-                    adder: adder.and_then(|x| x.parse::<isize>().ok()).unwrap(),
-                }
-            } else {
-                let step = 1 as usize;
-                let adder = 0 as isize;
+            parse_instruction(idx, line, mode, |_| None)
+        })
+        .collect()
+}
+
+/// Turns a program back into text `parse_program` accepts. Jumps keep their
+/// numeric offsets since the labels are gone after assembling.
+fn disassemble(program: &[Instruction]) -> String {
+    program
+        .iter()
+        .map(|instruction| format!("{}\n", instruction.op))
+        .collect()
+}
 
-                Instruction { idx, step, adder }
+struct Macro<'s> {
+    params: Vec<&'s str>,
+    body: Vec<&'s str>,
+}
+
+impl Macro<'_> {
+    /// The body line with every whole `%param` token swapped for its
+    /// argument, so `%n` is never mistaken for the start of `%nn`.
+    fn substitute(&self, line: &str, args: &[&str]) -> String {
+        let mut result = String::with_capacity(line.len());
+        let mut rest = line;
+
+        while let Some(at) = rest.find('%') {
+            result.push_str(&rest[..at]);
+            let after = &rest[at + 1..];
+            let len = after
+                .find(|chr: char| !(chr.is_alphanumeric() || chr == '_'))
+                .unwrap_or(after.len());
+
+            match self.params.iter().position(|param| *param == &after[..len]) {
+                Some(param) => result.push_str(args[param]),
+                None => result.push_str(&rest[at..at + 1 + len]),
             }
+            rest = &after[len..];
+        }
+        result.push_str(rest);
+
+        result
+    }
+}
+
+/// Whether `name` is an instruction in either mode, which a macro can't
+/// shadow.
+fn is_opcode(name: &str) -> bool {
+    matches!(name, "noop" | "jmp" | "jnz")
+        || ["add", "sub"].iter().any(|prefix| {
+            name.strip_prefix(prefix)
+                .is_some_and(|register| Register::from_name(register).is_some())
         })
-        .collect()
 }
 
-#[test]
-fn part1() {
-    let a = parse_input(INPUT);
+/// Assembles source with comments (`;` or `#` to the end of the line),
+/// `label:` lines that jumps can target, and macros:
+///
+/// ```text
+/// .macro wait ticks
+///     addy %ticks
+/// .endm
+/// ```
+///
+/// invoked as `wait 3`, each `%param` in the body replaced by its argument.
+/// Every instruction keeps the source line it came from (for a macro, the
+/// line it was invoked on) as its `idx`.
+fn assemble(source: &str, mode: Mode) -> Result<Vec<Instruction>, ParseError> {
+    const MAX_DEPTH: usize = 16;
 
-    let mut signal = Signal {
-        cycle: 0,
-        strength_during: 1,
-        strength_after: 1,
-    };
+    let mut macros = HashMap::new();
+    let mut lines = vec![];
+    let mut source_lines = source
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.split([';', '#']).next().unwrap_or("").trim()))
+        .filter(|(_, line)| !line.is_empty());
 
-    let b = a
-        .iter()
-        .map(|instruction| signal.execute_instruction(instruction))
-        .flat_map(|sig| sig)
+    while let Some((idx, line)) = source_lines.next() {
+        if let Some(header) = line.strip_prefix(".macro") {
+            let mut header = header.split_ascii_whitespace();
+            let name = header
+                .next()
+                .ok_or(ParseError::MissingOperand { line: idx })?;
+            if is_opcode(name) {
+                return Err(ParseError::ReservedMacroName {
+                    line: idx,
+                    name: name.to_string(),
+                });
+            }
+            let params = header.collect::<Vec<_>>();
+
+            let mut body = vec![];
+            loop {
+                match source_lines.next() {
+                    Some((_, ".endm")) => break,
+                    Some((_, line)) => body.push(line),
+                    None => return Err(ParseError::UnterminatedMacro { line: idx }),
+                }
+            }
+
+            macros.insert(name, Macro { params, body });
+        } else {
+            lines.push((idx, line.to_string()));
+        }
+    }
+
+    // expand macros until only labels and instructions are left
+    let mut expanded = vec![];
+    let mut pending = lines
+        .into_iter()
+        .rev()
+        .map(|(idx, line)| (idx, line, 0))
         .collect::<Vec<_>>();
 
-    let c = b
+    while let Some((idx, line, depth)) = pending.pop() {
+        let mut tokens = line.split_ascii_whitespace();
+        let Some(makro) = tokens.next().and_then(|name| macros.get(name)) else {
+            expanded.push((idx, line));
+            continue;
+        };
+
+        if depth == MAX_DEPTH {
+            return Err(ParseError::MacroTooDeep { line: idx });
+        }
+
+        let args = tokens.collect::<Vec<_>>();
+        if args.len() != makro.params.len() {
+            return Err(ParseError::MacroArguments {
+                line: idx,
+                expected: makro.params.len(),
+            });
+        }
+
+        for body_line in makro.body.iter().rev() {
+            pending.push((idx, makro.substitute(body_line, &args), depth + 1));
+        }
+    }
+
+    let mut labels = HashMap::new();
+    let mut instructions = vec![];
+
+    for (idx, line) in expanded.iter() {
+        if let Some(label) = line.strip_suffix(':') {
+            if labels.insert(label.trim(), instructions.len()).is_some() {
+                return Err(ParseError::DuplicateLabel {
+                    line: *idx,
+                    label: label.trim().to_string(),
+                });
+            }
+        } else {
+            instructions.push((*idx, line.as_str()));
+        }
+    }
+
+    instructions
         .iter()
-        .skip(19)
-        .step_by(40)
-        .map(|sig| sig.cycle as isize * sig.strength_during)
-        .sum::<isize>();
+        .enumerate()
+        .map(|(position, (idx, line))| {
+            parse_instruction(*idx, line, mode, |label| {
+                labels
+                    .get(label)
+                    .map(|target| *target as isize - position as isize)
+            })
+        })
+        .collect()
+}
 
-    println!("{:?}", c);
+type Hook<'a> = (
+    Box<dyn Fn(usize) -> bool + 'a>,
+    Box<dyn FnMut(&Signal) + 'a>,
+);
+
+/// The handheld's CPU, run one cycle at a time.
+struct Cpu<'a> {
+    program: &'a [Instruction],
+    pc: usize,
+    elapsed: usize,
+    cycle: usize,
+    registers: [isize; 4],
+    hooks: Vec<Hook<'a>>,
+}
+
+impl<'a> Cpu<'a> {
+    fn new(program: &'a [Instruction]) -> Self {
+        Self {
+            program,
+            pc: 0,
+            elapsed: 0,
+            cycle: 0,
+            registers: [1, 0, 0, 0],
+            hooks: vec![],
+        }
+    }
+
+    /// Calls `hook` with the state of every cycle `when` accepts,
+    /// e.g. `|cycle| cycle % 40 == 20` for the signal strength checks.
+    fn on_cycles(
+        &mut self,
+        when: impl Fn(usize) -> bool + 'a,
+        hook: impl FnMut(&Signal) + 'a,
+    ) -> &mut Self {
+        self.hooks.push((Box::new(when), Box::new(hook)));
+        self
+    }
+
+    fn x(&self) -> isize {
+        self.register(Register::X)
+    }
+
+    fn register(&self, register: Register) -> isize {
+        self.registers[register as usize]
+    }
+
+    fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    /// The instruction the next cycle belongs to, `None` once halted.
+    fn current_instruction(&self) -> Option<&'a Instruction> {
+        self.program.get(self.pc)
+    }
+
+    fn is_halted(&self) -> bool {
+        self.current_instruction().is_none()
+    }
+
+    /// Runs a single cycle. An instruction only changes X at the end of its
+    /// last cycle, so `addx` shows the old value during both of its cycles.
+    /// Jumping anywhere outside the program halts it.
+    fn tick(&mut self) -> Option<Signal> {
+        let instruction = self.current_instruction()?;
+
+        self.cycle += 1;
+        self.elapsed += 1;
+        let strength_during = self.x();
+
+        if self.elapsed == instruction.op.cycles() {
+            let mut offset = 1;
+
+            match instruction.op {
+                Op::Noop => {}
+                Op::Add(register, value) => self.registers[register as usize] += value,
+                Op::Sub(register, value) => self.registers[register as usize] -= value,
+                Op::Jmp(jump) => offset = jump,
+                Op::Jnz(register, jump) => {
+                    if self.register(register) != 0 {
+                        offset = jump
+                    }
+                }
+            }
+
+            self.pc = self
+                .pc
+                .checked_add_signed(offset)
+                .unwrap_or(self.program.len());
+            self.elapsed = 0;
+        }
+
+        let signal = Signal {
+            cycle: self.cycle,
+            strength_during,
+            strength_after: self.x(),
+        };
+
+        for (when, hook) in self.hooks.iter_mut() {
+            if when(signal.cycle) {
+                hook(&signal);
+            }
+        }
+
+        Some(signal)
+    }
+
+    /// Ticks until the program ends, returning every cycle.
+    fn run(&mut self) -> Vec<Signal> {
+        std::iter::from_fn(|| self.tick()).collect()
+    }
+
+    /// Like `run`, but stops after `cycles` at most, for programs that loop
+    /// forever.
+    fn run_for(&mut self, cycles: usize) -> Vec<Signal> {
+        std::iter::from_fn(|| self.tick()).take(cycles).collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Breakpoint {
+    Cycle(usize),
+    /// Stops after the first cycle of the instruction on this input line.
+    Instruction(usize),
+    /// Stops after the cycle in which the register changes to this value.
+    Register(Register, isize),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle(cycle) => write!(f, "cycle {cycle}"),
+            Self::Instruction(idx) => write!(f, "line {idx}"),
+            Self::Register(register, value) => write!(f, "{} {value}", register.name()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct TraceEntry {
+    instruction: Instruction,
+    signal: Signal,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cycle {}: {} (line {}), x during {}, x after {}",
+            self.signal.cycle,
+            self.instruction.op,
+            self.instruction.idx,
+            self.signal.strength_during,
+            self.signal.strength_after
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum StopReason {
+    Breakpoint(Breakpoint),
+    Halted,
+}
+
+/// Wraps a `Cpu` with breakpoints and a log of every cycle run so far.
+struct Debugger<'a> {
+    cpu: Cpu<'a>,
+    breakpoints: Vec<Breakpoint>,
+    trace: Vec<TraceEntry>,
+}
+
+impl<'a> Debugger<'a> {
+    fn new(program: &'a [Instruction]) -> Self {
+        Self {
+            cpu: Cpu::new(program),
+            breakpoints: vec![],
+            trace: vec![],
+        }
+    }
+
+    fn step(&mut self) -> Option<TraceEntry> {
+        let instruction = *self.cpu.current_instruction()?;
+        let signal = self.cpu.tick()?;
+
+        let entry = TraceEntry {
+            instruction,
+            signal,
+        };
+        self.trace.push(entry);
+
+        Some(entry)
+    }
+
+    /// `before` holds the registers as they were before the cycle in `entry`.
+    fn hit_breakpoint(
+        &self,
+        entry: &TraceEntry,
+        first_cycle: bool,
+        before: [isize; 4],
+    ) -> Option<Breakpoint> {
+        self.breakpoints
+            .iter()
+            .copied()
+            .find(|breakpoint| match breakpoint {
+                Breakpoint::Cycle(cycle) => entry.signal.cycle == *cycle,
+                Breakpoint::Instruction(idx) => entry.instruction.idx == *idx && first_cycle,
+                Breakpoint::Register(register, value) => {
+                    self.cpu.register(*register) == *value && before[*register as usize] != *value
+                }
+            })
+    }
+
+    /// Runs until a breakpoint is hit or the program ends.
+    fn resume(&mut self) -> StopReason {
+        loop {
+            let first_cycle = self.cpu.elapsed == 0;
+            let before = self.cpu.registers;
+
+            let Some(entry) = self.step() else {
+                return StopReason::Halted;
+            };
+
+            if let Some(breakpoint) = self.hit_breakpoint(&entry, first_cycle, before) {
+                return StopReason::Breakpoint(breakpoint);
+            }
+        }
+    }
+
+    /// Runs one command, returning `false` when the session should end.
+    ///
+    /// `break cycle N`, `break line N`, `break REGISTER N` (e.g. `break y 3`),
+    /// `delete`, `step [N]`,
+    /// `continue`, `print REGISTER`, `trace` and `quit` are understood, along
+    /// with the usual one letter shorthands.
+    fn execute(&mut self, command: &str, out: &mut impl Write) -> io::Result<bool> {
+        let tokens = command.split_ascii_whitespace().collect::<Vec<_>>();
+        let number = |token: &str| token.parse::<isize>().ok();
+
+        match tokens.as_slice() {
+            [] => {}
+            ["break" | "b", kind, value] => {
+                let register = Register::from_name(&kind.to_lowercase());
+                let breakpoint = match (*kind, register, number(value)) {
+                    ("cycle", _, Some(n)) if n > 0 => Some(Breakpoint::Cycle(n as usize)),
+                    ("line", _, Some(n)) if n > 0 => Some(Breakpoint::Instruction(n as usize)),
+                    (_, Some(register), Some(n)) => Some(Breakpoint::Register(register, n)),
+                    _ => None,
+                };
+
+                match breakpoint {
+                    Some(breakpoint) => {
+                        self.breakpoints.push(breakpoint);
+                        writeln!(out, "breakpoint on {breakpoint}")?;
+                    }
+                    None => writeln!(out, "usage: break cycle|line|x|y|z|w N")?,
+                }
+            }
+            ["delete" | "d"] => {
+                self.breakpoints.clear();
+                writeln!(out, "breakpoints cleared")?;
+            }
+            ["step" | "s", rest @ ..] => {
+                let count = match rest {
+                    [] => Some(1),
+                    [n] => number(n).filter(|n| *n > 0),
+                    _ => None,
+                };
+
+                match count {
+                    Some(count) => {
+                        for _ in 0..count {
+                            match self.step() {
+                                Some(entry) => writeln!(out, "{entry}")?,
+                                None => {
+                                    writeln!(out, "program halted at cycle {}", self.cpu.cycle())?;
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    None => writeln!(out, "usage: step [N]")?,
+                }
+            }
+            ["continue" | "c"] => match self.resume() {
+                StopReason::Breakpoint(breakpoint) => {
+                    if let Some(entry) = self.trace.last() {
+                        writeln!(out, "stopped on {breakpoint}")?;
+                        writeln!(out, "{entry}")?;
+                    }
+                }
+                StopReason::Halted => {
+                    writeln!(out, "program halted at cycle {}", self.cpu.cycle())?
+                }
+            },
+            ["print" | "p", name] => match Register::from_name(&name.to_lowercase()) {
+                Some(register) => writeln!(
+                    out,
+                    "{} = {} (cycle {})",
+                    register.name(),
+                    self.cpu.register(register),
+                    self.cpu.cycle()
+                )?,
+                None => writeln!(out, "unknown register: {name}")?,
+            },
+            ["trace" | "t"] => {
+                for entry in self.trace.iter() {
+                    writeln!(out, "{entry}")?;
+                }
+            }
+            ["quit" | "q"] => return Ok(false),
+            _ => writeln!(out, "unknown command: {}", command.trim())?,
+        }
+
+        Ok(true)
+    }
+
+    /// Reads commands line by line, e.g. from stdin, until `quit` or EOF.
+    fn run_session(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            if !self.execute(&line?, &mut out)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[test]
-fn part2() {
-    let a = parse_input(INPUT);
+fn part1() -> Result<(), ParseError> {
+    let a = parse_input(INPUT)?;
 
-    let mut signal = Signal {
-        cycle: 0,
-        strength_during: 1,
-        strength_after: 1,
-    };
+    let mut c = 0;
+    Cpu::new(&a)
+        .on_cycles(
+            |cycle| cycle % 40 == 20,
+            |sig| c += sig.cycle as isize * sig.strength_during,
+        )
+        .run();
 
-    let b = a
-        .iter()
-        .map(|instruction| signal.execute_instruction(instruction))
-        .flat_map(|sig| sig)
-        .collect::<Vec<_>>();
+    println!("{:?}", c);
+
+    Ok(())
+}
 
-    let screen = b.iter().map(|sig| sig.draw_pixel()).collect::<String>();
+#[test]
+fn part2() -> Result<(), ParseError> {
+    let a = parse_input(INPUT)?;
+
+    let mut crt = Crt::default();
+    Cpu::new(&a).on_cycles(|_| true, |sig| crt.draw(sig)).run();
+
+    let screen = crt.to_ascii();
 
     println!("{}", screen);
+    assert_eq!(ocr::recognise(&screen), Ok("EGLHBLFJ".to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn cpu() -> Result<(), ParseError> {
+    let program = parse_input("noop\naddx 3\naddx -5")?;
+    let mut cpu = Cpu::new(&program);
+
+    let expected = [(1, 1, 1), (2, 1, 1), (3, 1, 4), (4, 4, 4), (5, 4, -1)];
+    for (cycle, during, after) in expected {
+        assert_eq!(
+            cpu.tick(),
+            Some(Signal {
+                cycle,
+                strength_during: during,
+                strength_after: after
+            })
+        );
+        assert_eq!(cpu.x(), after);
+        assert_eq!(cpu.cycle(), cycle);
+    }
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.tick(), None);
+
+    let mut seen = vec![];
+    Cpu::new(&program)
+        .on_cycles(|cycle| cycle % 2 == 0, |sig| seen.push(sig.cycle))
+        .run();
+    assert_eq!(seen, vec![2, 4]);
+
+    let errors = [
+        (
+            "mulx 2",
+            ParseError::UnknownOpcode {
+                line: 1,
+                opcode: "mulx".to_string(),
+            },
+        ),
+        ("noop\naddx", ParseError::MissingOperand { line: 2 }),
+        ("addx one", ParseError::InvalidOperand { line: 1 }),
+        ("noop 1", ParseError::UnexpectedOperand { line: 1 }),
+    ];
+    for (input, expected) in errors {
+        assert_eq!(parse_input(input), Err(expected));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn debugger() -> io::Result<()> {
+    let program = parse_input("noop\naddx 3\naddx -5\nnoop\nnoop").unwrap();
+    let mut debugger = Debugger::new(&program);
+
+    let commands = "break line 3\n\
+                    break x -1\n\
+                    step 2\n\
+                    continue\n\
+                    print x\n\
+                    continue\n\
+                    delete\n\
+                    jump\n\
+                    continue\n\
+                    trace\n\
+                    quit\n\
+                    print x\n";
+    let mut out = vec![];
+    debugger.run_session(commands.as_bytes(), &mut out)?;
+
+    let expected = [
+        "breakpoint on line 3",
+        "breakpoint on x -1",
+        "cycle 1: noop (line 1), x during 1, x after 1",
+        "cycle 2: addx 3 (line 2), x during 1, x after 1",
+        "stopped on line 3",
+        "cycle 4: addx -5 (line 3), x during 4, x after 4",
+        "x = 4 (cycle 4)",
+        "stopped on x -1",
+        "cycle 5: addx -5 (line 3), x during 4, x after -1",
+        "breakpoints cleared",
+        "unknown command: jump",
+        "program halted at cycle 7",
+        "cycle 1: noop (line 1), x during 1, x after 1",
+        "cycle 2: addx 3 (line 2), x during 1, x after 1",
+        "cycle 3: addx 3 (line 2), x during 1, x after 4",
+        "cycle 4: addx -5 (line 3), x during 4, x after 4",
+        "cycle 5: addx -5 (line 3), x during 4, x after -1",
+        "cycle 6: noop (line 4), x during -1, x after -1",
+        "cycle 7: noop (line 5), x during -1, x after -1",
+    ];
+    assert_eq!(
+        String::from_utf8(out).unwrap().lines().collect::<Vec<_>>(),
+        expected
+    );
+
+    // breakpoints on the other registers of the extended set
+    let program = parse_program("addy 2\nnoop\naddy 1\naddx 5", Mode::Extended).unwrap();
+    let mut debugger = Debugger::new(&program);
+    let mut out = vec![];
+    debugger.run_session(
+        "break y 3\nbreak q 1\ncontinue\nprint y\ncontinue\n".as_bytes(),
+        &mut out,
+    )?;
+    assert_eq!(
+        String::from_utf8(out).unwrap().lines().collect::<Vec<_>>(),
+        [
+            "breakpoint on y 3",
+            "usage: break cycle|line|x|y|z|w N",
+            "stopped on y 3",
+            "cycle 5: addy 1 (line 3), x during 1, x after 1",
+            "y = 3 (cycle 5)",
+            "program halted at cycle 7",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn crt() -> io::Result<()> {
+    let program = parse_input(&"noop\n".repeat(8)).unwrap();
+    let mut crt = Crt::new(4, 3, 3);
+    Cpu::new(&program)
+        .on_cycles(|_| true, |sig| crt.draw(sig))
+        .run();
+
+    assert_eq!(crt.to_ascii(), "###.\n###.\n....\n");
+    assert_eq!(crt.to_unicode(), "███ \n    \n");
+
+    let mut pbm = vec![];
+    crt.write_pbm(2, &mut pbm)?;
+    assert_eq!(pbm, b"P4\n8 6\n\xfc\xfc\xfc\xfc\x00\x00");
+
+    // the last column is drawn too, the old renderer put a newline there
+    let program = parse_input(&format!("addx 37\n{}", "noop\n".repeat(38))).unwrap();
+    let mut crt = Crt::default();
+    Cpu::new(&program)
+        .on_cycles(|_| true, |sig| crt.draw(sig))
+        .run();
+    assert!(crt.pixel(39, 0));
+    assert!(crt
+        .to_ascii()
+        .starts_with(&format!("##{}###\n", ".".repeat(35))));
+
+    Ok(())
+}
+
+#[test]
+fn assembler() -> Result<(), ParseError> {
+    let source = "; draws a bar three pixels wide on every row
+.macro wait n
+    addy %n   # y only counts, it never reaches the screen
+.endm
+
+.macro pixel
+    noop
+.endm
+
+start:
+    wait 1
+    pixel
+    jmp start
+";
+
+    assert_eq!(
+        assemble(source, Mode::Standard),
+        Err(ParseError::UnknownOpcode {
+            line: 11,
+            opcode: "addy".to_string()
+        })
+    );
+
+    let program = assemble(source, Mode::Extended)?;
+    assert_eq!(disassemble(&program), "addy 1\nnoop\njmp -2\n");
+    assert_eq!(
+        program.iter().map(|i| i.idx).collect::<Vec<_>>(),
+        vec![11, 12, 13]
+    );
+    assert_eq!(
+        parse_program(&disassemble(&program), Mode::Extended)?.len(),
+        3
+    );
+
+    let mut crt = Crt::default();
+    let mut cpu = Cpu::new(&program);
+    cpu.on_cycles(|_| true, |sig| crt.draw(sig)).run_for(240);
+    assert!(!cpu.is_halted());
+    drop(cpu);
+    assert_eq!(crt.to_ascii(), format!("###{}\n", ".".repeat(37)).repeat(6));
+
+    // a countdown with jnz, then plain addx to move the sprite
+    let program = assemble(
+        "addz 3\nloop:\nsubz 1\njnz z loop\naddx 10\nnoop",
+        Mode::Extended,
+    )?;
+    let mut cpu = Cpu::new(&program);
+    let signals = cpu.run();
+    assert_eq!(cpu.register(Register::Z), 0);
+    assert_eq!(cpu.x(), 11);
+    assert_eq!(signals.len(), 2 + 3 * 4 + 2 + 1);
+
+    let errors = [
+        (
+            "jmp nowhere",
+            ParseError::UnknownLabel {
+                line: 1,
+                label: "nowhere".to_string(),
+            },
+        ),
+        (
+            "a:\na:",
+            ParseError::DuplicateLabel {
+                line: 2,
+                label: "a".to_string(),
+            },
+        ),
+        (".macro m\nnoop", ParseError::UnterminatedMacro { line: 1 }),
+        (
+            ".macro m a\nnoop\n.endm\nm",
+            ParseError::MacroArguments {
+                line: 4,
+                expected: 1,
+            },
+        ),
+        (
+            ".macro m\nm\n.endm\nm",
+            ParseError::MacroTooDeep { line: 4 },
+        ),
+        ("jnz q 1", ParseError::InvalidOperand { line: 1 }),
+        (
+            ".macro noop\naddx 1\n.endm\nnoop",
+            ParseError::ReservedMacroName {
+                line: 1,
+                name: "noop".to_string(),
+            },
+        ),
+        (
+            ".macro addw n\n.endm",
+            ParseError::ReservedMacroName {
+                line: 1,
+                name: "addw".to_string(),
+            },
+        ),
+    ];
+    for (source, expected) in errors {
+        assert_eq!(assemble(source, Mode::Extended), Err(expected));
+    }
+
+    // a parameter that is a prefix of another one
+    let program = assemble(
+        ".macro m n nn\naddx %nn\naddy %n%n\n.endm\nm 1 2",
+        Mode::Extended,
+    )?;
+    assert_eq!(disassemble(&program), "addx 2\naddy 11\n");
+
+    Ok(())
 }
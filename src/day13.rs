@@ -1,112 +1,569 @@
-use std::{
-    cmp::Ordering,
-    convert::Infallible,
-    str::{Chars, FromStr},
-};
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use crate::json::{Json, JsonError};
 
 static INPUT: &'static str = include_str!("./day13.txt");
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum Packet {
-    Num(i32),
+    Num(i64),
     List(Vec<Self>),
 }
 
 impl Packet {
-    /// i had to look up to the code by UncleScientist
-    /// because i don't quite get it on recursive parsing with FromStr
-    fn parse_into(input: &mut Chars) -> Self {
-        let mut result = Vec::new();
-        let mut num = -1i32;
-
-        while let Some(chr) = input.next() {
-            match chr {
-                '[' => result.push(Self::parse_into(input)),
-                ',' => {
-                    if num >= 0 {
-                        result.push(Self::Num(num));
-                        num = -1;
-                    }
+    /// The order the puzzle defines. A number meets a list as if it were
+    /// a one element list, so e.g. `1` and `[[1]]` come out `Equal` here
+    /// even though they aren't the same packet.
+    fn compare_packet(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Num(left), Self::Num(right)) => left.cmp(right),
+            (Self::List(left), Self::List(right)) => Self::compare_lists(left, right),
+            // borrowing the number as a slice saves building `List(vec![Num])`
+            (Self::Num(_), Self::List(right)) => {
+                Self::compare_lists(std::slice::from_ref(self), right)
+            }
+            (Self::List(left), Self::Num(_)) => {
+                Self::compare_lists(left, std::slice::from_ref(other))
+            }
+        }
+    }
+
+    fn compare_lists(left: &[Self], right: &[Self]) -> Ordering {
+        left.iter()
+            .zip(right)
+            .map(|(left, right)| left.compare_packet(right))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| left.len().cmp(&right.len()))
+    }
+
+    /// Orders by shape alone, numbers before lists. Only used to break ties
+    /// between packets `compare_packet` can't tell apart.
+    fn compare_structure(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Num(left), Self::Num(right)) => left.cmp(right),
+            (Self::Num(_), Self::List(_)) => Ordering::Less,
+            (Self::List(_), Self::Num(_)) => Ordering::Greater,
+            (Self::List(left), Self::List(right)) => left
+                .iter()
+                .zip(right)
+                .map(|(left, right)| left.compare_structure(right))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| left.len().cmp(&right.len())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum PacketErrorKind {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    NumberOutOfRange,
+    TrailingCharacters,
+}
+
+/// `position` is the byte offset into the parsed text where things went
+/// wrong.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct PacketError {
+    position: usize,
+    kind: PacketErrorKind,
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            PacketErrorKind::UnexpectedChar(chr) => write!(f, "unexpected {chr:?}")?,
+            PacketErrorKind::UnexpectedEnd => write!(f, "unexpected end of packet")?,
+            PacketErrorKind::NumberOutOfRange => write!(f, "number doesn't fit in 64 bits")?,
+            PacketErrorKind::TrailingCharacters => write!(f, "trailing characters")?,
+        }
+        write!(f, " at position {}", self.position)
+    }
+}
+
+/// Recursive descent over `value := number | '[' (value (',' value)*)? ']'`,
+/// with whitespace allowed between any two tokens.
+struct PacketParser<'s> {
+    input: &'s str,
+    position: usize,
+}
+
+impl<'s> PacketParser<'s> {
+    fn new(input: &'s str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn error(&self, kind: PacketErrorKind) -> PacketError {
+        PacketError {
+            position: self.position,
+            kind,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(chr) = self.peek().filter(|chr| chr.is_whitespace()) {
+            self.position += chr.len_utf8();
+        }
+    }
+
+    fn expect_value(&mut self) -> Result<(), PacketError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('[' | '-' | '0'..='9') => Ok(()),
+            Some(chr) => Err(self.error(PacketErrorKind::UnexpectedChar(chr))),
+            None => Err(self.error(PacketErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    /// A whole packet, with nothing but whitespace after it.
+    fn parse(mut self) -> Result<Packet, PacketError> {
+        let packet = self.parse_value()?;
+
+        self.skip_whitespace();
+        if self.position < self.input.len() {
+            return Err(self.error(PacketErrorKind::TrailingCharacters));
+        }
+
+        Ok(packet)
+    }
+
+    fn parse_value(&mut self) -> Result<Packet, PacketError> {
+        self.expect_value()?;
+
+        if self.peek() == Some('[') {
+            self.parse_list()
+        } else {
+            self.parse_number().map(Packet::Num)
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Packet, PacketError> {
+        // the opening bracket
+        self.position += 1;
+        let mut result = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Packet::List(result));
+        }
+
+        loop {
+            result.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Packet::List(result));
                 }
-                ']' => {
-                    if num >= 0 {
-                        result.push(Self::Num(num));
+                Some(chr) => return Err(self.error(PacketErrorKind::UnexpectedChar(chr))),
+                None => return Err(self.error(PacketErrorKind::UnexpectedEnd)),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<i64, PacketError> {
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+
+        let digits = self.input[self.position..]
+            .bytes()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+
+        if digits == 0 {
+            return Err(match self.peek() {
+                Some(chr) => self.error(PacketErrorKind::UnexpectedChar(chr)),
+                None => self.error(PacketErrorKind::UnexpectedEnd),
+            });
+        }
+        self.position += digits;
+
+        self.input[start..self.position]
+            .parse::<i64>()
+            .map_err(|_| PacketError {
+                position: start,
+                kind: PacketErrorKind::NumberOutOfRange,
+            })
+    }
+
+    /// Same grammar and errors as `parse`, but straight into tokens, so no
+    /// list ever gets allocated.
+    fn parse_tokens(mut self) -> Result<Vec<Token>, PacketError> {
+        let mut tokens = vec![];
+        let mut depth = 0usize;
+
+        loop {
+            self.expect_value()?;
+
+            if self.peek() == Some('[') {
+                self.position += 1;
+                tokens.push(Token::Open);
+
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.position += 1;
+                    tokens.push(Token::Close);
+                } else {
+                    depth += 1;
+                    continue;
+                }
+            } else {
+                tokens.push(Token::Num(self.parse_number()?));
+            }
+
+            // a value just ended, close lists until one continues
+            loop {
+                self.skip_whitespace();
+
+                if depth == 0 {
+                    if self.position < self.input.len() {
+                        return Err(self.error(PacketErrorKind::TrailingCharacters));
                     }
-                    return Self::List(result);
+                    return Ok(tokens);
                 }
-                '0'..='9' => {
-                    if num == -1 {
-                        num = (chr as u8 - b'0') as i32;
-                    } else {
-                        num = (num * 10) + (chr as u8 - b'0') as i32;
+
+                match self.peek() {
+                    Some(',') => {
+                        self.position += 1;
+                        break;
+                    }
+                    Some(']') => {
+                        self.position += 1;
+                        tokens.push(Token::Close);
+                        depth -= 1;
                     }
+                    Some(chr) => return Err(self.error(PacketErrorKind::UnexpectedChar(chr))),
+                    None => return Err(self.error(PacketErrorKind::UnexpectedEnd)),
                 }
-                _ => unreachable!(),
             }
         }
-        Self::List(result)
     }
+}
 
-    fn compare_packet(&self, other: &Self) -> Ordering {
-        match (self, other) {
-            (Self::List(left), Self::List(right)) => {
-                let mut idx = 0;
-                while idx < left.len() && idx < right.len() {
-                    match (&left[idx], &right[idx]) {
-                        (Self::Num(l), Self::Num(r)) => {
-                            if l != r {
-                                return l.cmp(r);
-                            }
-                        }
-                        (Self::List(_), Self::Num(r)) => {
-                            let check = left[idx].compare_packet(&Self::List(vec![Self::Num(*r)]));
-                            if check != Ordering::Equal {
-                                return check;
-                            }
-                        }
-                        (Self::Num(l), Self::List(_)) => {
-                            let check = Self::List(vec![Self::Num(*l)]).compare_packet(&right[idx]);
-                            if check != Ordering::Equal {
-                                return check;
-                            }
-                        }
-                        (Self::List(_), Self::List(_)) => {
-                            let check = left[idx].compare_packet(&right[idx]);
-                            if check != Ordering::Equal {
-                                return check;
-                            }
-                        }
+impl FromStr for Packet {
+    type Err = PacketError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PacketParser::new(s).parse()
+    }
+}
+
+impl fmt::Display for Packet {
+    /// The canonical form from the puzzle input, e.g. `[1,[2,3]]`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Num(num) => write!(f, "{num}"),
+            Self::List(list) => {
+                write!(f, "[")?;
+                for (idx, packet) in list.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
                     }
-                    idx += 1;
+                    write!(f, "{packet}")?;
                 }
-                left.len().cmp(&right.len())
+                write!(f, "]")
             }
-            _ => unreachable!(),
         }
     }
 }
 
-impl FromStr for Packet {
-    type Err = Infallible;
+impl Packet {
+    /// Spreads the packet over several lines, two spaces per level. A list
+    /// that fits in `width` columns, indentation included, stays on one line.
+    fn pretty(&self, width: usize) -> String {
+        let mut result = String::new();
+        self.pretty_into(width, 0, &mut result);
+        result
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut chars = s.chars();
-        Ok(Self::parse_into(&mut chars))
+    fn pretty_into(&self, width: usize, depth: usize, result: &mut String) {
+        let inline = self.to_string();
+        let list = match self {
+            Self::List(list) if depth * 2 + inline.len() > width && !list.is_empty() => list,
+            _ => {
+                result.push_str(&inline);
+                return;
+            }
+        };
+
+        result.push_str("[\n");
+        for (idx, packet) in list.iter().enumerate() {
+            result.push_str(&"  ".repeat(depth + 1));
+            packet.pretty_into(width, depth + 1, result);
+            if idx + 1 < list.len() {
+                result.push(',');
+            }
+            result.push('\n');
+        }
+        result.push_str(&"  ".repeat(depth));
+        result.push(']');
     }
 }
 
 impl PartialOrd for Packet {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.compare_packet(other))
+        Some(self.cmp(other))
     }
 }
 
+/// The puzzle's order, with ties broken by `compare_structure` so that only
+/// identical packets compare `Equal`, as `Ord` requires.
 impl Ord for Packet {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.compare_packet(other)
+            .then_with(|| self.compare_structure(other))
+    }
+}
+
+/// The variant order makes the derived `Ord` on token streams match
+/// `compare_structure`: a closing bracket (shorter list) first, then
+/// numbers, then lists.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+enum Token {
+    Close,
+    Num(i64),
+    Open,
+}
+
+/// A packet flattened into its brackets and numbers, one allocation no
+/// matter how deep it nests.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct FlatPacket {
+    tokens: Vec<Token>,
+}
+
+/// Walks a token stream, with room to splice in the brackets of a number
+/// being promoted to a list.
+struct TokenCursor<'a> {
+    tokens: std::slice::Iter<'a, Token>,
+    injected: Vec<Token>,
+}
+
+impl<'a> TokenCursor<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        Self {
+            tokens: tokens.iter(),
+            injected: vec![],
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.injected.pop().or_else(|| self.tokens.next().copied())
+    }
+
+    /// Replays `num` followed by a closing bracket, as if it had been
+    /// written `[num]` and the opening bracket was just read.
+    fn promote(&mut self, num: i64) {
+        self.injected.extend([Token::Close, Token::Num(num)]);
+    }
+}
+
+impl FlatPacket {
+    /// The puzzle order, like `Packet::compare_packet`, worked out token by
+    /// token without building either tree.
+    fn compare_packet(&self, other: &Self) -> Ordering {
+        let mut left = TokenCursor::new(&self.tokens);
+        let mut right = TokenCursor::new(&other.tokens);
+
+        loop {
+            match (left.next(), right.next()) {
+                (Some(l), Some(r)) => match (l, r) {
+                    (Token::Num(l), Token::Num(r)) if l != r => return l.cmp(&r),
+                    (Token::Num(_), Token::Num(_))
+                    | (Token::Open, Token::Open)
+                    | (Token::Close, Token::Close) => {}
+                    (Token::Close, _) => return Ordering::Less,
+                    (_, Token::Close) => return Ordering::Greater,
+                    (Token::Num(num), Token::Open) => left.promote(num),
+                    (Token::Open, Token::Num(num)) => right.promote(num),
+                },
+                // both sides are balanced, so they run out together
+                (l, r) => return l.is_some().cmp(&r.is_some()),
+            }
+        }
+    }
+
+    fn to_packet(&self) -> Packet {
+        let mut stack = vec![vec![]];
+
+        for token in self.tokens.iter() {
+            match token {
+                Token::Open => stack.push(vec![]),
+                Token::Num(num) => stack.last_mut().unwrap().push(Packet::Num(*num)),
+                Token::Close => {
+                    let list = stack.pop().unwrap();
+                    stack.last_mut().unwrap().push(Packet::List(list));
+                }
+            }
+        }
+
+        stack.pop().unwrap().pop().unwrap()
+    }
+}
+
+impl From<&Packet> for FlatPacket {
+    fn from(packet: &Packet) -> Self {
+        fn flatten(packet: &Packet, tokens: &mut Vec<Token>) {
+            match packet {
+                Packet::Num(num) => tokens.push(Token::Num(*num)),
+                Packet::List(list) => {
+                    tokens.push(Token::Open);
+                    for item in list {
+                        flatten(item, tokens);
+                    }
+                    tokens.push(Token::Close);
+                }
+            }
+        }
+
+        let mut tokens = vec![];
+        flatten(packet, &mut tokens);
+        Self { tokens }
+    }
+}
+
+impl FromStr for FlatPacket {
+    type Err = PacketError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            tokens: PacketParser::new(s).parse_tokens()?,
+        })
+    }
+}
+
+impl fmt::Display for FlatPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut previous = None;
+
+        for token in self.tokens.iter() {
+            let separated =
+                matches!(previous, Some(Token::Num(_) | Token::Close)) && *token != Token::Close;
+            if separated {
+                write!(f, ",")?;
+            }
+
+            match token {
+                Token::Open => write!(f, "[")?,
+                Token::Close => write!(f, "]")?,
+                Token::Num(num) => write!(f, "{num}")?,
+            }
+            previous = Some(*token);
+        }
+
+        Ok(())
+    }
+}
+
+impl Ord for FlatPacket {
+    /// Same order as `Packet`: the puzzle's, with ties broken by structure.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.compare_packet(other)
+            .then_with(|| self.tokens.cmp(&other.tokens))
+    }
+}
+
+impl PartialOrd for FlatPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum JsonPacketError {
+    Json(JsonError),
+    /// A value packets have no room for, with a path like `$[3][0]`
+    /// pointing at it.
+    Unsupported {
+        path: String,
+        kind: &'static str,
+    },
+}
+
+impl fmt::Display for JsonPacketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "{err}"),
+            Self::Unsupported { path, kind } => {
+                write!(f, "packets can't hold the {kind} at {path}")
+            }
+        }
+    }
+}
+
+impl From<JsonError> for JsonPacketError {
+    fn from(err: JsonError) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<&Packet> for Json {
+    fn from(packet: &Packet) -> Self {
+        match packet {
+            Packet::Num(num) => Json::Int(*num),
+            Packet::List(list) => Json::Array(list.iter().map(Json::from).collect()),
+        }
+    }
+}
+
+impl Packet {
+    fn from_json(json: &Json, path: &mut String) -> Result<Self, JsonPacketError> {
+        match json {
+            Json::Int(num) => Ok(Self::Num(*num)),
+            Json::Array(array) => {
+                let mut result = Vec::with_capacity(array.len());
+
+                for (idx, value) in array.iter().enumerate() {
+                    let len = path.len();
+                    path.push_str(&format!("[{idx}]"));
+                    result.push(Self::from_json(value, path)?);
+                    path.truncate(len);
+                }
+
+                Ok(Self::List(result))
+            }
+            other => Err(JsonPacketError::Unsupported {
+                path: path.clone(),
+                kind: other.kind(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<&Json> for Packet {
+    type Error = JsonPacketError;
+
+    fn try_from(json: &Json) -> Result<Self, Self::Error> {
+        Self::from_json(json, &mut "$".to_string())
     }
 }
 
+/// Reads a JSON array whose items are packets, e.g. `[[1,2],[[3]]]`.
+fn packets_from_json(s: &str) -> Result<Vec<Packet>, JsonPacketError> {
+    match Packet::try_from(&s.parse::<Json>()?)? {
+        Packet::List(packets) => Ok(packets),
+        Packet::Num(_) => Err(JsonPacketError::Unsupported {
+            path: "$".to_string(),
+            kind: "integer",
+        }),
+    }
+}
+
+/// Writes packets as one JSON array, the reverse of `packets_from_json`.
+fn packets_to_json(packets: &[Packet]) -> String {
+    Json::Array(packets.iter().map(Json::from).collect()).to_string()
+}
+
 #[derive(Debug, PartialEq, Clone)]
 struct Signal {
     left_packet: Packet,
@@ -115,47 +572,296 @@ struct Signal {
 
 impl IntoIterator for Signal {
     type Item = Packet;
-    type IntoIter = SignalIterator;
+    type IntoIter = std::array::IntoIter<Packet, 2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        [self.left_packet, self.right_packet].into_iter()
+    }
+}
+
+impl Signal {
+    fn iter(&self) -> std::array::IntoIter<&Packet, 2> {
+        [&self.left_packet, &self.right_packet].into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Signal {
+    type Item = &'a Packet;
+    type IntoIter = std::array::IntoIter<&'a Packet, 2>;
 
     fn into_iter(self) -> Self::IntoIter {
-        SignalIterator {
-            signal: self,
-            index: 0,
+        self.iter()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Left => write!(f, "left"),
+            Self::Right => write!(f, "right"),
         }
     }
 }
 
-struct SignalIterator {
-    signal: Signal,
-    index: usize,
+/// Why a comparison stopped, named after the side that came out smaller.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Rule {
+    SmallerInteger(Side),
+    RanOut(Side),
 }
 
-impl Iterator for SignalIterator {
-    type Item = Packet;
+impl Rule {
+    fn ordering(self) -> Ordering {
+        match self {
+            Self::SmallerInteger(Side::Left) | Self::RanOut(Side::Left) => Ordering::Less,
+            Self::SmallerInteger(Side::Right) | Self::RanOut(Side::Right) => Ordering::Greater,
+        }
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let result = match self.index {
-            0 => self.signal.left_packet.clone(),
-            1 => self.signal.right_packet.clone(),
-            _ => return None,
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self.ordering() {
+            Ordering::Less => "in the right order",
+            _ => "not in the right order",
         };
-        self.index += 1;
-        Some(result)
+        match self {
+            Self::SmallerInteger(Side::Left) => write!(f, "Left side is smaller"),
+            Self::SmallerInteger(Side::Right) => write!(f, "Right side is smaller"),
+            Self::RanOut(Side::Left) => write!(f, "Left side ran out of items"),
+            Self::RanOut(Side::Right) => write!(f, "Right side ran out of items"),
+        }?;
+        write!(f, ", so inputs are {verdict}")
+    }
+}
+
+/// A number wrapped into a list so it could be compared with one. `path`
+/// holds the indices leading to the number in its own packet.
+#[derive(Debug, PartialEq, Clone)]
+struct Promotion {
+    side: Side,
+    path: Vec<usize>,
+}
+
+/// One side of a comparison step, either straight from the packet or a
+/// number that got promoted to a one item list.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Operand<'a> {
+    Packet(&'a Packet),
+    Promoted(&'a Packet),
+}
+
+impl<'a> Operand<'a> {
+    fn items(self) -> Option<&'a [Packet]> {
+        match self {
+            Self::Packet(Packet::List(list)) => Some(list),
+            Self::Packet(Packet::Num(_)) => None,
+            Self::Promoted(packet) => Some(std::slice::from_ref(packet)),
+        }
     }
 }
 
-fn parse_input(s: &str) -> Vec<Signal> {
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Packet(packet) => write!(f, "{packet}"),
+            Self::Promoted(packet) => write!(f, "[{packet}]"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Step<'a> {
+    Compare(Operand<'a>, Operand<'a>),
+    Promote(Side, Operand<'a>),
+    Decide(Rule),
+}
+
+/// Every step `compare_packet` takes on a pair, for explaining the result.
+/// The paths hold the indices into each packet where the decision fell;
+/// a promoted number adds no index of its own.
+#[derive(Debug, PartialEq, Clone)]
+struct Trace<'a> {
+    steps: Vec<(usize, Step<'a>)>,
+    promotions: Vec<Promotion>,
+    rule: Option<Rule>,
+    left_path: Vec<usize>,
+    right_path: Vec<usize>,
+}
+
+impl<'a> Trace<'a> {
+    fn new(left: &'a Packet, right: &'a Packet) -> Self {
+        let mut trace = Self {
+            steps: vec![],
+            promotions: vec![],
+            rule: None,
+            left_path: vec![],
+            right_path: vec![],
+        };
+
+        trace.rule = trace.walk(Operand::Packet(left), Operand::Packet(right), 0);
+        if trace.rule.is_none() {
+            trace.left_path.clear();
+            trace.right_path.clear();
+        }
+
+        trace
+    }
+
+    fn ordering(&self) -> Ordering {
+        self.rule.map_or(Ordering::Equal, Rule::ordering)
+    }
+
+    fn walk(&mut self, left: Operand<'a>, right: Operand<'a>, depth: usize) -> Option<Rule> {
+        self.steps.push((depth, Step::Compare(left, right)));
+
+        match (left.items(), right.items()) {
+            (None, None) => {
+                let (Operand::Packet(Packet::Num(l)), Operand::Packet(Packet::Num(r))) =
+                    (left, right)
+                else {
+                    unreachable!("only plain numbers have no items")
+                };
+                let rule = match l.cmp(r) {
+                    Ordering::Less => Rule::SmallerInteger(Side::Left),
+                    Ordering::Greater => Rule::SmallerInteger(Side::Right),
+                    Ordering::Equal => return None,
+                };
+                self.steps.push((depth + 1, Step::Decide(rule)));
+                Some(rule)
+            }
+            (None, Some(_)) => self.promote(Side::Left, left, right, depth),
+            (Some(_), None) => self.promote(Side::Right, left, right, depth),
+            (Some(left_items), Some(right_items)) => {
+                for (idx, (l, r)) in left_items.iter().zip(right_items).enumerate() {
+                    // the promoted side stays on its number, there is no list to index
+                    let left_indexed = matches!(left, Operand::Packet(_));
+                    let right_indexed = matches!(right, Operand::Packet(_));
+                    if left_indexed {
+                        self.left_path.push(idx);
+                    }
+                    if right_indexed {
+                        self.right_path.push(idx);
+                    }
+
+                    if let Some(rule) = self.walk(Operand::Packet(l), Operand::Packet(r), depth + 1)
+                    {
+                        return Some(rule);
+                    }
+
+                    if left_indexed {
+                        self.left_path.pop();
+                    }
+                    if right_indexed {
+                        self.right_path.pop();
+                    }
+                }
+
+                let rule = match left_items.len().cmp(&right_items.len()) {
+                    Ordering::Less => Rule::RanOut(Side::Left),
+                    Ordering::Greater => Rule::RanOut(Side::Right),
+                    Ordering::Equal => return None,
+                };
+                self.steps.push((depth + 1, Step::Decide(rule)));
+                Some(rule)
+            }
+        }
+    }
+
+    fn promote(
+        &mut self,
+        side: Side,
+        left: Operand<'a>,
+        right: Operand<'a>,
+        depth: usize,
+    ) -> Option<Rule> {
+        let (left, right, promoted, path) = match (side, left, right) {
+            (Side::Left, Operand::Packet(num), _) => (
+                Operand::Promoted(num),
+                right,
+                Operand::Promoted(num),
+                &self.left_path,
+            ),
+            (Side::Right, _, Operand::Packet(num)) => (
+                left,
+                Operand::Promoted(num),
+                Operand::Promoted(num),
+                &self.right_path,
+            ),
+            _ => unreachable!("promoted operands are already lists"),
+        };
+
+        self.promotions.push(Promotion {
+            side,
+            path: path.clone(),
+        });
+        self.steps.push((depth + 1, Step::Promote(side, promoted)));
+
+        self.walk(left, right, depth + 1)
+    }
+}
+
+/// Renders the steps the way the puzzle explains its example pairs.
+impl fmt::Display for Trace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (line, (depth, step)) in self.steps.iter().enumerate() {
+            if line > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:indent$}- ", "", indent = depth * 2)?;
+
+            match step {
+                Step::Compare(left, right) => write!(f, "Compare {left} vs {right}")?,
+                Step::Promote(side, value) => write!(
+                    f,
+                    "Mixed types; convert {side} to {value} and retry comparison"
+                )?,
+                Step::Decide(rule) => write!(f, "{rule}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Signal {
+    /// Why the pair is (or isn't) in the right order.
+    fn explain(&self) -> Trace<'_> {
+        Trace::new(&self.left_packet, &self.right_packet)
+    }
+}
+
+fn parse_input(s: &str) -> Result<Vec<Signal>, PacketError> {
     s.split("\n\n")
         .map(|blocks| {
             let mut line = blocks.lines();
 
-            let a = line.next().unwrap();
-            let b = line.next().unwrap();
+            let a = line.next().unwrap_or_default();
+            let b = line.next().unwrap_or_default();
 
-            Signal {
-                left_packet: a.parse::<Packet>().unwrap(),
-                right_packet: b.parse::<Packet>().unwrap(),
-            }
+            Ok(Signal {
+                left_packet: a.parse::<Packet>()?,
+                right_packet: b.parse::<Packet>()?,
+            })
+        })
+        .collect()
+}
+
+fn parse_flat_input(s: &str) -> Result<Vec<(FlatPacket, FlatPacket)>, PacketError> {
+    s.split("\n\n")
+        .map(|blocks| {
+            let mut line = blocks.lines();
+
+            let a = line.next().unwrap_or_default();
+            let b = line.next().unwrap_or_default();
+
+            Ok((a.parse()?, b.parse()?))
         })
         .collect()
 }
@@ -163,17 +869,33 @@ fn parse_input(s: &str) -> Vec<Signal> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::rng::Lcg;
+
+    /// A packet nested at most `depth` lists deep, with numbers below
+    /// `values` and lists shorter than `len`. Small limits make ties under
+    /// the puzzle's rules come up often.
+    fn random_packet(rng: &mut Lcg, depth: usize, values: u64, len: u64) -> Packet {
+        if depth == 0 || rng.below(3) == 0 {
+            Packet::Num(rng.below(values) as i64)
+        } else {
+            Packet::List(
+                (0..rng.below(len))
+                    .map(|_| random_packet(rng, depth - 1, values, len))
+                    .collect(),
+            )
+        }
+    }
 
     #[test]
     fn part1() {
-        let a = parse_input(INPUT);
+        let a = parse_input(INPUT).unwrap();
 
         let b = a
             .iter()
             .enumerate()
             .map(|(idx, signal)| {
                 let mut marker = 0;
-                if signal.left_packet < signal.right_packet {
+                if signal.left_packet.compare_packet(&signal.right_packet) == Ordering::Less {
                     marker += idx + 1;
                 }
                 marker
@@ -185,25 +907,327 @@ mod test {
 
     #[test]
     fn part2() {
-        let a = parse_input(INPUT);
+        let a = parse_input(INPUT).unwrap();
 
         let two = Packet::from_str("[[2]]").unwrap();
         let six = Packet::from_str("[[6]]").unwrap();
 
-        let mut b = a
-            .iter()
-            .map(|signal| signal.clone().into_iter())
-            .flat_map(|s| s)
-            .collect::<Vec<_>>();
+        let mut b = a.iter().flatten().collect::<Vec<_>>();
 
-        b.push(two.clone());
-        b.push(six.clone());
+        b.push(&two);
+        b.push(&six);
 
         b.sort();
 
-        let position_of_two = b.iter().position(|packet| *packet == two).unwrap() + 1;
-        let position_of_six = b.iter().position(|packet| *packet == six).unwrap() + 1;
+        let position_of_two = b.iter().position(|packet| **packet == two).unwrap() + 1;
+        let position_of_six = b.iter().position(|packet| **packet == six).unwrap() + 1;
 
         println!("{:?}", position_of_two * position_of_six);
     }
+
+    #[test]
+    fn round_trip() {
+        for line in INPUT.lines().filter(|line| !line.is_empty()) {
+            let packet = line.parse::<Packet>().unwrap();
+
+            assert_eq!(packet.to_string(), line);
+            assert_eq!(packet.pretty(usize::MAX), line);
+        }
+
+        let packet = Packet::from_str("[1,[2,[3,[]]],4]").unwrap();
+        assert_eq!(
+            packet.pretty(10),
+            "[\n  1,\n  [\n    2,\n    [3,[]]\n  ],\n  4\n]"
+        );
+        assert_eq!(packet.pretty(0).lines().count(), 11);
+    }
+
+    #[test]
+    fn total_order() {
+        use Packet::{List, Num};
+
+        assert_eq!(Num(1).cmp(&Num(2)), Ordering::Less);
+        assert_eq!(Num(3).cmp(&List(vec![Num(2), Num(9)])), Ordering::Greater);
+        assert_eq!(List(vec![]).cmp(&Num(0)), Ordering::Less);
+        assert_eq!(
+            Num(1).compare_packet(&List(vec![List(vec![Num(1)])])),
+            Ordering::Equal
+        );
+        assert_eq!(Num(1).cmp(&List(vec![Num(1)])), Ordering::Less);
+
+        let mut rng = Lcg::new(7);
+        let packets = (0..60)
+            .map(|_| random_packet(&mut rng, 4, 3, 3))
+            .collect::<Vec<_>>();
+
+        for a in packets.iter() {
+            for b in packets.iter() {
+                assert_eq!(a.cmp(b), b.cmp(a).reverse());
+                assert_eq!(a.cmp(b) == Ordering::Equal, a == b);
+                assert_eq!(a.compare_packet(b), b.compare_packet(a).reverse());
+
+                for c in packets.iter() {
+                    if a <= b && b <= c {
+                        assert!(a <= c, "{a} <= {b} <= {c}");
+                    }
+                    if a.compare_packet(b).is_le() && b.compare_packet(c).is_le() {
+                        assert!(a.compare_packet(c).is_le(), "{a} <= {b} <= {c}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn explain() {
+        let example = "[1,1,3,1,1]\n[1,1,5,1,1]\n\n[[1],[2,3,4]]\n[[1],4]\n\n\
+            [9]\n[[8,7,6]]\n\n[7,7,7,7]\n[7,7,7]\n\n[[]]\n[[]]";
+        let signals = parse_input(example).unwrap();
+
+        let trace = signals[1].explain();
+        assert_eq!(
+            trace.to_string(),
+            [
+                "- Compare [[1],[2,3,4]] vs [[1],4]",
+                "  - Compare [1] vs [1]",
+                "    - Compare 1 vs 1",
+                "  - Compare [2,3,4] vs 4",
+                "    - Mixed types; convert right to [4] and retry comparison",
+                "    - Compare [2,3,4] vs [4]",
+                "      - Compare 2 vs 4",
+                "        - Left side is smaller, so inputs are in the right order",
+            ]
+            .join("\n")
+        );
+        assert_eq!(trace.rule, Some(Rule::SmallerInteger(Side::Left)));
+        assert_eq!((trace.left_path, trace.right_path), (vec![1, 0], vec![1]));
+        assert_eq!(
+            trace.promotions,
+            vec![Promotion {
+                side: Side::Right,
+                path: vec![1]
+            }]
+        );
+
+        let trace = signals[2].explain();
+        assert_eq!(
+            trace.to_string(),
+            [
+                "- Compare [9] vs [[8,7,6]]",
+                "  - Compare 9 vs [8,7,6]",
+                "    - Mixed types; convert left to [9] and retry comparison",
+                "    - Compare [9] vs [8,7,6]",
+                "      - Compare 9 vs 8",
+                "        - Right side is smaller, so inputs are not in the right order",
+            ]
+            .join("\n")
+        );
+        assert_eq!((trace.left_path, trace.right_path), (vec![0], vec![0, 0]));
+
+        let trace = signals[3].explain();
+        assert_eq!(trace.rule, Some(Rule::RanOut(Side::Right)));
+        assert!(trace
+            .to_string()
+            .ends_with("\n  - Right side ran out of items, so inputs are not in the right order"));
+
+        let trace = signals[4].explain();
+        assert_eq!(trace.ordering(), Ordering::Equal);
+        assert_eq!(trace.rule, None);
+
+        assert_eq!(signals[0].explain().ordering(), Ordering::Less);
+
+        for signal in parse_input(INPUT).unwrap() {
+            assert_eq!(
+                signal.explain().ordering(),
+                signal.left_packet.compare_packet(&signal.right_packet)
+            );
+        }
+    }
+
+    #[test]
+    fn json() {
+        let packets = parse_input(INPUT)
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let json = packets_to_json(&packets);
+        assert_eq!(packets_from_json(&json), Ok(packets));
+
+        assert_eq!(
+            packets_from_json(" [ [1, [2, -3]], [] ]\n"),
+            Ok(vec![
+                Packet::from_str("[1,[2,-3]]").unwrap(),
+                Packet::from_str("[]").unwrap()
+            ])
+        );
+
+        let errors = [
+            (
+                "[[1,[2,\"x\"]]]",
+                "packets can't hold the string at $[0][1][1]",
+            ),
+            ("[[1.5]]", "packets can't hold the float at $[0][0]"),
+            ("[{\"a\":1}]", "packets can't hold the object at $[0]"),
+            ("7", "packets can't hold the integer at $"),
+            ("[[1]", "unexpected end of JSON at position 4"),
+            (
+                "[[99999999999999999999]]",
+                "integer out of range at position 2",
+            ),
+        ];
+        for (input, message) in errors {
+            assert_eq!(packets_from_json(input).unwrap_err().to_string(), message);
+        }
+    }
+
+    #[test]
+    fn flat_packets() {
+        use Packet::{List, Num};
+
+        for line in INPUT.lines().filter(|line| !line.is_empty()) {
+            let flat = line.parse::<FlatPacket>().unwrap();
+            let packet = line.parse::<Packet>().unwrap();
+
+            assert_eq!(flat.to_string(), line);
+            assert_eq!(flat.to_packet(), packet);
+            assert_eq!(FlatPacket::from(&packet), flat);
+        }
+
+        let flat = |packet: &Packet| FlatPacket::from(packet);
+        assert_eq!(
+            flat(&Num(1)).compare_packet(&flat(&List(vec![List(vec![Num(1)])]))),
+            Ordering::Equal
+        );
+        assert_eq!(
+            flat(&List(vec![Num(1), Num(2)]))
+                .compare_packet(&flat(&List(vec![List(vec![Num(1)])]))),
+            Ordering::Greater
+        );
+        assert_eq!(flat(&List(vec![])).to_string(), "[]");
+
+        let mut rng = Lcg::new(11);
+        let packets = (0..60)
+            .map(|_| random_packet(&mut rng, 4, 3, 3))
+            .collect::<Vec<_>>();
+        let flats = packets.iter().map(FlatPacket::from).collect::<Vec<_>>();
+
+        for (a, flat_a) in packets.iter().zip(flats.iter()) {
+            for (b, flat_b) in packets.iter().zip(flats.iter()) {
+                assert_eq!(flat_a.compare_packet(flat_b), a.compare_packet(b));
+                assert_eq!(flat_a.cmp(flat_b), a.cmp(b));
+            }
+        }
+
+        let pairs = parse_flat_input(INPUT).unwrap();
+        for ((left, right), signal) in pairs.iter().zip(parse_input(INPUT).unwrap()) {
+            assert_eq!(
+                left.compare_packet(right),
+                signal.left_packet.compare_packet(&signal.right_packet)
+            );
+        }
+
+        for input in [
+            "[1,2", "[1,2]]", "[1 2]", "[1,,2]", "[1,]", "[-]", "", "[[[]] ,]",
+        ] {
+            assert_eq!(
+                input.parse::<FlatPacket>().unwrap_err(),
+                input.parse::<Packet>().unwrap_err()
+            );
+        }
+    }
+
+    /// Timings for both representations on a large generated input. Run
+    /// with `cargo test --release day13 -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_flat_packets() {
+        use std::time::Instant;
+
+        let mut rng = Lcg::new(3);
+        let mut input = String::new();
+        for _ in 0..20_000 {
+            let left = random_packet(&mut rng, 8, 11, 5);
+            let right = random_packet(&mut rng, 8, 11, 5);
+            input.push_str(&format!("{left}\n{right}\n\n"));
+        }
+        let input = input.trim_end();
+
+        let start = Instant::now();
+        let signals = parse_input(input).unwrap();
+        let parsed = start.elapsed();
+        let start = Instant::now();
+        let ordered = signals
+            .iter()
+            .filter(|signal| {
+                signal
+                    .left_packet
+                    .compare_packet(&signal.right_packet)
+                    .is_lt()
+            })
+            .count();
+        let compared = start.elapsed();
+        let mut packets = signals.iter().flatten().collect::<Vec<_>>();
+        let start = Instant::now();
+        packets.sort();
+        let sorted = start.elapsed();
+        println!("tree: parse {parsed:?}, compare {compared:?}, sort {sorted:?}");
+
+        let start = Instant::now();
+        let pairs = parse_flat_input(input).unwrap();
+        let parsed = start.elapsed();
+        let start = Instant::now();
+        let flat_ordered = pairs
+            .iter()
+            .filter(|(left, right)| left.compare_packet(right).is_lt())
+            .count();
+        let compared = start.elapsed();
+        let mut flats = pairs.iter().flat_map(|(l, r)| [l, r]).collect::<Vec<_>>();
+        let start = Instant::now();
+        flats.sort();
+        let sorted = start.elapsed();
+        println!("flat: parse {parsed:?}, compare {compared:?}, sort {sorted:?}");
+
+        assert_eq!(ordered, flat_ordered);
+        assert!(packets
+            .iter()
+            .zip(flats.iter())
+            .all(|(packet, flat)| FlatPacket::from(*packet) == **flat));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let packet = " [ -1 , [ 9223372036854775807 ] ,[]] ".parse::<Packet>();
+        assert_eq!(
+            packet.map(|p| p.to_string()),
+            Ok("[-1,[9223372036854775807],[]]".to_string())
+        );
+        assert_eq!("7".parse::<Packet>(), Ok(Packet::Num(7)));
+
+        let errors = [
+            ("[1,2", 4, PacketErrorKind::UnexpectedEnd),
+            ("[1,2]]", 5, PacketErrorKind::TrailingCharacters),
+            ("[1 2]", 3, PacketErrorKind::UnexpectedChar('2')),
+            ("[1,,2]", 3, PacketErrorKind::UnexpectedChar(',')),
+            ("[1,]", 3, PacketErrorKind::UnexpectedChar(']')),
+            ("[a]", 1, PacketErrorKind::UnexpectedChar('a')),
+            ("[-]", 2, PacketErrorKind::UnexpectedChar(']')),
+            (
+                "[99999999999999999999]",
+                1,
+                PacketErrorKind::NumberOutOfRange,
+            ),
+            ("", 0, PacketErrorKind::UnexpectedEnd),
+        ];
+
+        for (input, position, kind) in errors {
+            assert_eq!(input.parse::<Packet>(), Err(PacketError { position, kind }));
+        }
+
+        assert_eq!(
+            "[1,2".parse::<Packet>().unwrap_err().to_string(),
+            "unexpected end of packet at position 4"
+        );
+    }
 }
@@ -0,0 +1,118 @@
+use std::io::{self, Write};
+
+fn check_size(width: usize, height: usize, len: usize) -> io::Result<()> {
+    if width * height == len {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{width}x{height} image can't hold {len} pixels"),
+        ))
+    }
+}
+
+/// Black and white (`P4`), where `true` is black.
+#[allow(dead_code)]
+pub fn write_pbm(
+    out: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[bool],
+) -> io::Result<()> {
+    check_size(width, height, pixels.len())?;
+
+    write!(out, "P4\n{width} {height}\n")?;
+    for row in pixels.chunks(width.max(1)) {
+        // every row starts on a fresh byte, most significant bit first
+        let packed = row
+            .chunks(8)
+            .map(|bits| {
+                bits.iter()
+                    .enumerate()
+                    .fold(0u8, |byte, (idx, bit)| byte | ((*bit as u8) << (7 - idx)))
+            })
+            .collect::<Vec<_>>();
+        out.write_all(&packed)?;
+    }
+
+    Ok(())
+}
+
+/// Grayscale (`P5`), where `max_value` is white.
+#[allow(dead_code)]
+pub fn write_pgm(
+    out: &mut impl Write,
+    width: usize,
+    height: usize,
+    max_value: u8,
+    pixels: &[u8],
+) -> io::Result<()> {
+    check_size(width, height, pixels.len())?;
+
+    write!(out, "P5\n{width} {height}\n{max_value}\n")?;
+    out.write_all(pixels)
+}
+
+/// Colour (`P6`), one `[r, g, b]` per pixel.
+#[allow(dead_code)]
+pub fn write_ppm(
+    out: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[[u8; 3]],
+) -> io::Result<()> {
+    check_size(width, height, pixels.len())?;
+
+    write!(out, "P6\n{width} {height}\n255\n")?;
+    out.write_all(&pixels.concat())
+}
+
+/// Blows every pixel up into a `factor` by `factor` square, so small grids
+/// are still visible when opened.
+#[allow(dead_code)]
+pub fn upscale<T: Copy>(width: usize, pixels: &[T], factor: usize) -> Vec<T> {
+    if width == 0 {
+        return vec![];
+    }
+
+    pixels
+        .chunks(width)
+        .flat_map(|row| {
+            let row = row
+                .iter()
+                .flat_map(|pixel| std::iter::repeat_n(*pixel, factor))
+                .collect::<Vec<_>>();
+            std::iter::repeat_n(row, factor).flatten()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode() -> io::Result<()> {
+        let mut pgm = vec![];
+        write_pgm(&mut pgm, 2, 1, 9, &[0, 9])?;
+        assert_eq!(pgm, b"P5\n2 1\n9\n\x00\x09");
+
+        let mut ppm = vec![];
+        write_ppm(&mut ppm, 1, 1, &[[1, 2, 3]])?;
+        assert_eq!(ppm, b"P6\n1 1\n255\n\x01\x02\x03");
+
+        let mut pbm = vec![];
+        let pixels = [[true; 9], [false; 9]].concat();
+        write_pbm(&mut pbm, 9, 2, &pixels)?;
+        assert_eq!(pbm, b"P4\n9 2\n\xff\x80\x00\x00");
+
+        assert!(write_pgm(&mut vec![], 2, 2, 1, &[0]).is_err());
+
+        assert_eq!(
+            upscale(2, &[1, 2, 3, 4], 2),
+            vec![1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]
+        );
+
+        Ok(())
+    }
+}
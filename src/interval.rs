@@ -0,0 +1,497 @@
+use std::fmt;
+
+/// Inclusive range `start..=end` of `u64`s, never empty. Being inclusive it
+/// can reach `u64::MAX` without overflowing.
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Interval {
+    start: u64,
+    end: u64,
+}
+
+#[allow(dead_code)]
+impl Interval {
+    /// `None` when `start` comes after `end`.
+    pub fn new(start: u64, end: u64) -> Option<Self> {
+        (start <= end).then_some(Self { start, end })
+    }
+
+    pub fn point(value: u64) -> Self {
+        Self {
+            start: value,
+            end: value,
+        }
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Number of values covered, which is `2^64` for the full range.
+    pub fn len(&self) -> u128 {
+        (self.end - self.start) as u128 + 1
+    }
+
+    pub fn contains_point(&self, value: u64) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    /// Whether all of `other` lies inside this interval.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// Overlapping, or ending right where the other one starts.
+    pub fn touches(&self, other: &Self) -> bool {
+        self.start <= other.end.saturating_add(1) && other.start <= self.end.saturating_add(1)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        Self::new(self.start.max(other.start), self.end.min(other.end))
+    }
+
+    /// The two as one interval, or `None` if there is a gap between them.
+    pub fn union(&self, other: &Self) -> Option<Self> {
+        self.touches(other).then(|| self.hull(other))
+    }
+
+    /// Smallest interval covering both, gap included.
+    pub fn hull(&self, other: &Self) -> Self {
+        Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    /// Same `start-end` form the puzzle input uses.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// A union of intervals, kept sorted with every overlapping or adjacent
+/// pair merged into one.
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct IntervalSet {
+    intervals: Vec<Interval>,
+}
+
+#[allow(dead_code)]
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, interval: Interval) {
+        // everything from `first` up to `last` merges with the new interval
+        let first = self
+            .intervals
+            .partition_point(|other| other.end.saturating_add(1) < interval.start);
+        let last = first
+            + self.intervals[first..]
+                .iter()
+                .take_while(|other| other.touches(&interval))
+                .count();
+
+        let merged = self.intervals[first..last]
+            .iter()
+            .fold(interval, |merged, other| merged.hull(other));
+        self.intervals.splice(first..last, [merged]);
+    }
+
+    /// The merged intervals, in order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Interval> {
+        self.intervals.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Number of values covered by the whole set.
+    pub fn covered(&self) -> u128 {
+        self.intervals.iter().map(Interval::len).sum()
+    }
+
+    /// The merged interval `value` falls in, if any.
+    fn find(&self, value: u64) -> Option<&Interval> {
+        let idx = self.intervals.partition_point(|other| other.end < value);
+        self.intervals
+            .get(idx)
+            .filter(|other| other.contains_point(value))
+    }
+
+    pub fn contains_point(&self, value: u64) -> bool {
+        self.find(value).is_some()
+    }
+
+    /// Whether all of `interval` is covered. Merged intervals never touch,
+    /// so it has to fit inside a single one of them.
+    pub fn contains(&self, interval: &Interval) -> bool {
+        self.find(interval.start)
+            .is_some_and(|other| other.contains(interval))
+    }
+
+    pub fn overlaps(&self, interval: &Interval) -> bool {
+        let idx = self
+            .intervals
+            .partition_point(|other| other.end < interval.start);
+        self.intervals
+            .get(idx)
+            .is_some_and(|other| other.overlaps(interval))
+    }
+
+    /// Everything in `within` the set doesn't cover.
+    pub fn gaps(&self, within: &Interval) -> Self {
+        let mut intervals = vec![];
+        let mut next = Some(within.start);
+
+        for covered in self.iter().filter(|other| other.overlaps(within)) {
+            if let Some(start) = next.filter(|start| *start < covered.start) {
+                intervals.push(Interval {
+                    start,
+                    end: covered.start - 1,
+                });
+            }
+            next = covered.end.checked_add(1);
+        }
+        if let Some(start) = next.filter(|start| *start <= within.end) {
+            intervals.push(Interval {
+                start,
+                end: within.end,
+            });
+        }
+
+        Self { intervals }
+    }
+}
+
+/// Static index over intervals that each carry a value, answering overlap
+/// queries in `O(log n + k)`.
+///
+/// The entries are sorted by start and read as an implicit balanced tree:
+/// the middle of every range is a node, and each node remembers the
+/// largest end below it, so whole subtrees ending before a query are
+/// skipped.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct IntervalIndex<T> {
+    entries: Vec<(Interval, T)>,
+    max_end: Vec<u64>,
+}
+
+#[allow(dead_code)]
+impl<T> IntervalIndex<T> {
+    pub fn new(mut entries: Vec<(Interval, T)>) -> Self {
+        entries.sort_by_key(|(interval, _)| *interval);
+
+        fn build<T>(entries: &[(Interval, T)], max_end: &mut [u64], lo: usize, hi: usize) -> u64 {
+            if lo >= hi {
+                return 0;
+            }
+            let mid = (lo + hi) / 2;
+            let end = build(entries, max_end, lo, mid)
+                .max(build(entries, max_end, mid + 1, hi))
+                .max(entries[mid].0.end);
+            max_end[mid] = end;
+            end
+        }
+
+        let mut max_end = vec![0; entries.len()];
+        build(&entries, &mut max_end, 0, entries.len());
+
+        Self { entries, max_end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every entry sharing at least one value with `query`, ordered by
+    /// interval.
+    pub fn overlapping(&self, query: &Interval) -> Vec<&(Interval, T)> {
+        let mut result = vec![];
+        self.collect_overlapping(query, 0, self.entries.len(), &mut result);
+        result
+    }
+
+    fn collect_overlapping<'a>(
+        &'a self,
+        query: &Interval,
+        lo: usize,
+        hi: usize,
+        result: &mut Vec<&'a (Interval, T)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        if self.max_end[mid] < query.start {
+            return;
+        }
+
+        self.collect_overlapping(query, lo, mid, result);
+        // everything right of `mid` starts at or after it
+        if self.entries[mid].0.start <= query.end {
+            if self.entries[mid].0.overlaps(query) {
+                result.push(&self.entries[mid]);
+            }
+            self.collect_overlapping(query, mid + 1, hi, result);
+        }
+    }
+
+    /// Sweeps over all entries and returns how many cover each stretch of
+    /// values, in order. Stretches nobody covers are left out.
+    pub fn depths(&self) -> Vec<(Interval, usize)> {
+        // depth changes at every start, and right after every end
+        let mut events = self
+            .entries
+            .iter()
+            .flat_map(|(interval, _)| {
+                [
+                    Some((interval.start, 1)),
+                    interval.end.checked_add(1).map(|after| (after, -1)),
+                ]
+            })
+            .flatten()
+            .collect::<Vec<(u64, isize)>>();
+        events.sort_unstable();
+
+        let mut result = vec![];
+        let mut depth = 0isize;
+        let mut idx = 0;
+
+        while idx < events.len() {
+            let position = events[idx].0;
+            while idx < events.len() && events[idx].0 == position {
+                depth += events[idx].1;
+                idx += 1;
+            }
+
+            if depth > 0 {
+                let end = events.get(idx).map_or(u64::MAX, |(next, _)| next - 1);
+                result.push((
+                    Interval {
+                        start: position,
+                        end,
+                    },
+                    depth as usize,
+                ));
+            }
+        }
+
+        result
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = Interval>>(iter: T) -> Self {
+        let mut set = Self::new();
+        for interval in iter {
+            set.insert(interval);
+        }
+        set
+    }
+}
+
+impl Extend<Interval> for IntervalSet {
+    fn extend<T: IntoIterator<Item = Interval>>(&mut self, iter: T) {
+        for interval in iter {
+            self.insert(interval);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a IntervalSet {
+    type Item = &'a Interval;
+    type IntoIter = std::slice::Iter<'a, Interval>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rng::Lcg;
+
+    fn interval(start: u64, end: u64) -> Interval {
+        Interval::new(start, end).unwrap()
+    }
+
+    #[test]
+    fn intervals() {
+        assert_eq!(Interval::new(5, 4), None);
+        assert_eq!(Interval::point(3), interval(3, 3));
+
+        let a = interval(2, 8);
+        let b = interval(3, 7);
+        let c = interval(6, 9);
+        let d = interval(9, 12);
+
+        assert!(a.contains(&b) && !b.contains(&a));
+        assert!(a.overlaps(&c) && !b.overlaps(&d));
+        assert_eq!(a.intersection(&c), Some(interval(6, 8)));
+        assert_eq!(a.intersection(&d), None);
+        assert_eq!(a.union(&d), Some(interval(2, 12)));
+        assert_eq!(b.union(&d), None);
+        assert_eq!(b.hull(&d), interval(3, 12));
+        assert_eq!(a.to_string(), "2-8");
+
+        let full = interval(0, u64::MAX);
+        assert_eq!(full.len(), 1 << 64);
+        assert!(full.contains(&interval(u64::MAX, u64::MAX)));
+        assert_eq!(
+            interval(u64::MAX - 1, u64::MAX).union(&interval(0, 3)),
+            None
+        );
+        assert_eq!(interval(5, u64::MAX).union(&interval(0, 4)), Some(full));
+    }
+
+    #[test]
+    fn sets() {
+        let mut set = [interval(10, 20), interval(30, 40), interval(1, 2)]
+            .into_iter()
+            .collect::<IntervalSet>();
+        assert_eq!(set.iter().count(), 3);
+
+        set.insert(interval(21, 29));
+        assert_eq!(
+            set.iter().copied().collect::<Vec<_>>(),
+            vec![interval(1, 2), interval(10, 40)]
+        );
+
+        set.insert(interval(0, 50));
+        assert_eq!(
+            set.iter().copied().collect::<Vec<_>>(),
+            vec![interval(0, 50)]
+        );
+
+        set.extend([interval(u64::MAX - 3, u64::MAX), interval(60, 60)]);
+        assert_eq!(set.covered(), 51 + 4 + 1);
+        assert!(set.contains(&interval(5, 45)));
+        assert!(!set.contains(&interval(45, 60)));
+        assert!(set.overlaps(&interval(45, 60)));
+        assert!(!set.overlaps(&interval(51, 59)));
+        assert!(set.contains_point(u64::MAX) && !set.contains_point(61));
+
+        // against a plain bitmap over a small universe
+        let mut rng = Lcg::new(5);
+        let mut set = IntervalSet::new();
+        let mut covered = [false; 200];
+        for _ in 0..150 {
+            let start = rng.below(195);
+            let end = start + rng.below(6);
+            set.insert(interval(start, end));
+            covered[start as usize..=end as usize].fill(true);
+
+            for pair in set.iter().collect::<Vec<_>>().windows(2) {
+                assert!(!pair[0].touches(pair[1]) && pair[0] < pair[1]);
+            }
+            for value in 0..200 {
+                assert_eq!(set.contains_point(value), covered[value as usize]);
+            }
+        }
+        assert_eq!(
+            set.covered(),
+            covered.iter().filter(|lit| **lit).count() as u128
+        );
+    }
+
+    #[test]
+    fn index() {
+        let index = IntervalIndex::new(vec![
+            (interval(5, 9), 'a'),
+            (interval(1, 3), 'b'),
+            (interval(2, 12), 'c'),
+            (interval(20, u64::MAX), 'd'),
+            (interval(7, 7), 'e'),
+        ]);
+
+        let labels = |query: Interval| {
+            index
+                .overlapping(&query)
+                .iter()
+                .map(|(_, label)| *label)
+                .collect::<String>()
+        };
+        assert_eq!(labels(interval(0, 0)), "");
+        assert_eq!(labels(interval(3, 5)), "bca");
+        assert_eq!(labels(interval(7, 8)), "cae");
+        assert_eq!(labels(interval(13, 19)), "");
+        assert_eq!(labels(interval(u64::MAX, u64::MAX)), "d");
+
+        assert_eq!(
+            index.depths(),
+            vec![
+                (interval(1, 1), 1),
+                (interval(2, 3), 2),
+                (interval(4, 4), 1),
+                (interval(5, 6), 2),
+                (interval(7, 7), 3),
+                (interval(8, 9), 2),
+                (interval(10, 12), 1),
+                (interval(20, u64::MAX), 1),
+            ]
+        );
+
+        let covered = index
+            .depths()
+            .into_iter()
+            .map(|(interval, _)| interval)
+            .collect::<IntervalSet>();
+        assert_eq!(
+            covered
+                .gaps(&interval(0, u64::MAX))
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![interval(0, 0), interval(13, 19)]
+        );
+        assert_eq!(
+            covered
+                .gaps(&interval(11, 15))
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![interval(13, 15)]
+        );
+
+        // against a linear scan
+        let mut rng = Lcg::new(9);
+        let mut entries = vec![];
+        for label in 0..300 {
+            let start = rng.below(1000);
+            entries.push((interval(start, start + rng.below(40)), label));
+        }
+        let index = IntervalIndex::new(entries.clone());
+
+        for _ in 0..200 {
+            let start = rng.below(1050);
+            let query = interval(start, start + rng.below(30));
+
+            let mut expected = entries
+                .iter()
+                .filter(|(interval, _)| interval.overlaps(&query))
+                .collect::<Vec<_>>();
+            expected.sort();
+            assert_eq!(index.overlapping(&query), expected);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+/// Sliding windows of exactly `size` items over any iterator.
+///
+/// The items are kept in one buffer and every window is lent out as a slice
+/// of it, so nothing gets cloned per step. Because the slice borrows the
+/// adapter this can't be a plain `Iterator`, use `next_window` or
+/// `position` instead.
+#[allow(dead_code)]
+pub struct Windows<I: Iterator> {
+    size: usize,
+    step: usize,
+    iter: I,
+    buffer: Vec<I::Item>,
+    started: bool,
+}
+
+/// Windows over `iter`, each one `size` items long and moving by one item.
+#[allow(dead_code)]
+pub fn windows_of<I: IntoIterator>(size: usize, iter: I) -> Windows<I::IntoIter> {
+    assert!(size > 0, "window size must be non-zero");
+
+    Windows {
+        size,
+        step: 1,
+        iter: iter.into_iter(),
+        buffer: Vec::with_capacity(size * 2),
+        started: false,
+    }
+}
+
+impl<I: Iterator> Windows<I> {
+    /// Moves every window `step` items forward instead of one. With a step
+    /// larger than the window the items in between are skipped.
+    #[allow(dead_code)]
+    pub fn step(mut self, step: usize) -> Self {
+        assert!(step > 0, "step must be non-zero");
+
+        self.step = step;
+        self
+    }
+
+    #[allow(dead_code)]
+    fn pull(&mut self) -> Option<()> {
+        let item = self.iter.next()?;
+
+        // keep the buffer bounded by dropping what is behind the window
+        if self.buffer.len() == self.buffer.capacity() {
+            let stale = self.buffer.len() + 1 - self.size;
+            self.buffer.drain(..stale);
+        }
+        self.buffer.push(item);
+
+        Some(())
+    }
+
+    /// Lends the next window, or `None` once fewer than `size` items are
+    /// left. A source shorter than `size` yields no window at all.
+    #[allow(dead_code)]
+    pub fn next_window(&mut self) -> Option<&[I::Item]> {
+        let wanted = if self.started { self.step } else { self.size };
+
+        for _ in 0..wanted {
+            if self.pull().is_none() {
+                // a partial window is never handed out
+                self.buffer.clear();
+                return None;
+            }
+        }
+        self.started = true;
+
+        Some(&self.buffer[self.buffer.len() - self.size..])
+    }
+
+    /// Index of the first window matching `predicate`.
+    #[allow(dead_code)]
+    pub fn position(&mut self, mut predicate: impl FnMut(&[I::Item]) -> bool) -> Option<usize> {
+        let mut idx = 0;
+
+        while let Some(window) = self.next_window() {
+            if predicate(window) {
+                return Some(idx);
+            }
+            idx += 1;
+        }
+
+        None
+    }
+}
+
+/// Windows borrowed straight out of a slice, with a step size.
+///
+/// Unlike `Windows` the source outlives the adapter, so this one is a normal
+/// `Iterator` over `&[T]`.
+#[allow(dead_code)]
+pub struct SliceWindows<'a, T> {
+    slice: &'a [T],
+    size: usize,
+    step: usize,
+}
+
+/// Windows over `slice`, each one `size` items long and moving by one item.
+#[allow(dead_code)]
+pub fn slice_windows<T>(slice: &[T], size: usize) -> SliceWindows<'_, T> {
+    assert!(size > 0, "window size must be non-zero");
+
+    SliceWindows {
+        slice,
+        size,
+        step: 1,
+    }
+}
+
+impl<'a, T> SliceWindows<'a, T> {
+    #[allow(dead_code)]
+    pub fn step(mut self, step: usize) -> Self {
+        assert!(step > 0, "step must be non-zero");
+
+        self.step = step;
+        self
+    }
+}
+
+impl<'a, T> Iterator for SliceWindows<'a, T> {
+    type Item = &'a [T];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.slice.len() < self.size {
+            return None;
+        }
+
+        let window = &self.slice[..self.size];
+        self.slice = &self.slice[self.step.min(self.slice.len())..];
+
+        Some(window)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn collect<I: Iterator>(mut windows: Windows<I>) -> Vec<Vec<I::Item>>
+    where
+        I::Item: Clone,
+    {
+        let mut result = vec![];
+        while let Some(window) = windows.next_window() {
+            result.push(window.to_vec());
+        }
+        result
+    }
+
+    #[test]
+    fn exact_length() {
+        let windows = collect(windows_of(3, 1..=5));
+
+        assert_eq!(windows, vec![vec![1, 2, 3], vec![2, 3, 4], vec![3, 4, 5]]);
+    }
+
+    #[test]
+    fn short_input() {
+        assert!(collect(windows_of(4, 1..=3)).is_empty());
+        assert!(collect(windows_of(1, 0..0)).is_empty());
+        assert_eq!(collect(windows_of(3, 1..=3)), vec![vec![1, 2, 3]]);
+
+        assert_eq!(slice_windows(&[1, 2, 3], 4).count(), 0);
+        assert_eq!(slice_windows::<u8>(&[], 1).count(), 0);
+    }
+
+    #[test]
+    fn with_step() {
+        let windows = collect(windows_of(2, 1..=7).step(3));
+        assert_eq!(windows, vec![vec![1, 2], vec![4, 5]]);
+
+        let windows = collect(windows_of(3, 1..=6).step(2));
+        assert_eq!(windows, vec![vec![1, 2, 3], vec![3, 4, 5]]);
+
+        let data = [1, 2, 3, 4, 5, 6];
+        let windows = slice_windows(&data, 3).step(2).collect::<Vec<_>>();
+        assert_eq!(windows, vec![&[1, 2, 3][..], &[3, 4, 5][..]]);
+    }
+
+    #[test]
+    fn long_input_matches_slice_windows() {
+        let data = (0..1000).map(|i| i * 7 % 13).collect::<Vec<_>>();
+
+        for size in [1, 2, 5, 14] {
+            let expected = data.windows(size).map(|w| w.to_vec()).collect::<Vec<_>>();
+
+            assert_eq!(collect(windows_of(size, data.iter().copied())), expected);
+            assert_eq!(
+                slice_windows(&data, size)
+                    .map(|w| w.to_vec())
+                    .collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+}
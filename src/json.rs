@@ -0,0 +1,472 @@
+use std::{fmt, str::FromStr};
+
+/// A JSON document. Integers that fit in an `i64` are kept apart from other
+/// numbers, so callers can tell `1` from `1.0`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys stay in the order they were written.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// What kind of value this is, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool(_) => "boolean",
+            Self::Int(_) => "integer",
+            Self::Float(_) => "float",
+            Self::String(_) => "string",
+            Self::Array(_) => "array",
+            Self::Object(_) => "object",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum JsonErrorKind {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    InvalidNumber,
+    /// An integer literal too large for `i64`.
+    IntegerOutOfRange,
+    InvalidEscape,
+    TrailingCharacters,
+}
+
+/// `position` is a byte offset into the parsed text.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct JsonError {
+    pub position: usize,
+    pub kind: JsonErrorKind,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            JsonErrorKind::UnexpectedChar(chr) => write!(f, "unexpected {chr:?}")?,
+            JsonErrorKind::UnexpectedEnd => write!(f, "unexpected end of JSON")?,
+            JsonErrorKind::InvalidNumber => write!(f, "invalid number")?,
+            JsonErrorKind::IntegerOutOfRange => write!(f, "integer out of range")?,
+            JsonErrorKind::InvalidEscape => write!(f, "invalid escape sequence")?,
+            JsonErrorKind::TrailingCharacters => write!(f, "trailing characters")?,
+        }
+        write!(f, " at position {}", self.position)
+    }
+}
+
+struct JsonParser<'s> {
+    input: &'s str,
+    position: usize,
+}
+
+impl<'s> JsonParser<'s> {
+    fn error(&self, kind: JsonErrorKind) -> JsonError {
+        JsonError {
+            position: self.position,
+            kind,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn unexpected(&self) -> JsonError {
+        match self.peek() {
+            Some(chr) => self.error(JsonErrorKind::UnexpectedChar(chr)),
+            None => self.error(JsonErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(chr) = self
+            .peek()
+            .filter(|chr| matches!(chr, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.position += chr.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_object(),
+            Some('"') => self.parse_string().map(Json::String),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("null", Json::Null),
+                    ("true", Json::Bool(true)),
+                    ("false", Json::Bool(false)),
+                ] {
+                    if self.input[self.position..].starts_with(word) {
+                        self.position += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.unexpected())
+            }
+            None => Err(self.unexpected()),
+        }
+    }
+
+    /// Shared by arrays and objects: `open`, items separated by commas, `close`.
+    fn parse_sequence<T>(
+        &mut self,
+        open: char,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<T, JsonError>,
+    ) -> Result<Vec<T>, JsonError> {
+        self.expect(open)?;
+        let mut result = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(close) {
+            self.position += 1;
+            return Ok(result);
+        }
+
+        loop {
+            result.push(item(self)?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(chr) if chr == close => {
+                    self.position += 1;
+                    return Ok(result);
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, JsonError> {
+        self.parse_sequence('[', ']', Self::parse_value)
+            .map(Json::Array)
+    }
+
+    fn parse_object(&mut self) -> Result<Json, JsonError> {
+        self.parse_sequence('{', '}', |parser| {
+            parser.skip_whitespace();
+            let key = parser.parse_string()?;
+            parser.expect(':')?;
+            Ok((key, parser.parse_value()?))
+        })
+        .map(Json::Object)
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut result = String::new();
+
+        loop {
+            let chr = self
+                .peek()
+                .ok_or(self.error(JsonErrorKind::UnexpectedEnd))?;
+
+            match chr {
+                '"' => {
+                    self.position += 1;
+                    return Ok(result);
+                }
+                '\\' => {
+                    self.position += 1;
+                    result.push(self.parse_escape()?);
+                }
+                chr if (chr as u32) < 0x20 => return Err(self.unexpected()),
+                chr => {
+                    self.position += chr.len_utf8();
+                    result.push(chr);
+                }
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, JsonError> {
+        let invalid = self.error(JsonErrorKind::InvalidEscape);
+        let escaped = self
+            .peek()
+            .ok_or(self.error(JsonErrorKind::UnexpectedEnd))?;
+        self.position += escaped.len_utf8();
+
+        let chr = match escaped {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = self.parse_hex()?;
+
+                // characters outside the basic plane come as a surrogate pair
+                if (0xd800..0xdc00).contains(&high) {
+                    if !self.input[self.position..].starts_with("\\u") {
+                        return Err(invalid);
+                    }
+                    self.position += 2;
+                    let low = self.parse_hex()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(invalid);
+                    }
+                    char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+                        .ok_or(invalid)?
+                } else {
+                    char::from_u32(high).ok_or(invalid)?
+                }
+            }
+            _ => return Err(invalid),
+        };
+
+        Ok(chr)
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .input
+            .get(self.position..self.position + 4)
+            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or(self.error(JsonErrorKind::InvalidEscape))?;
+        self.position += 4;
+
+        Ok(u32::from_str_radix(digits, 16).unwrap_or_default())
+    }
+
+    fn parse_number(&mut self) -> Result<Json, JsonError> {
+        let start = self.position;
+        let rest = &self.input[start..];
+        let length = rest
+            .find(|chr: char| !matches!(chr, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+            .unwrap_or(rest.len());
+        let text = &rest[..length];
+
+        let invalid = self.error(JsonErrorKind::InvalidNumber);
+        let bytes = text.as_bytes();
+        let mut at = 0;
+        let digits = |at: &mut usize| {
+            let count = bytes[*at..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit())
+                .count();
+            *at += count;
+            count
+        };
+
+        // -? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?, so no
+        // leading `+`, no leading zeros and no bare `.5`, `1.` or `1.e5`
+        if bytes.first() == Some(&b'-') {
+            at += 1;
+        }
+        let integer_start = at;
+        let integer_digits = digits(&mut at);
+        if integer_digits == 0 || (integer_digits > 1 && bytes[integer_start] == b'0') {
+            return Err(invalid);
+        }
+
+        let integer = at == bytes.len();
+        if bytes.get(at) == Some(&b'.') {
+            at += 1;
+            if digits(&mut at) == 0 {
+                return Err(invalid);
+            }
+        }
+        if matches!(bytes.get(at), Some(b'e' | b'E')) {
+            at += 1;
+            if matches!(bytes.get(at), Some(b'+' | b'-')) {
+                at += 1;
+            }
+            if digits(&mut at) == 0 {
+                return Err(invalid);
+            }
+        }
+        if at != bytes.len() {
+            return Err(invalid);
+        }
+
+        if integer {
+            let int = text
+                .parse::<i64>()
+                .map_err(|_| self.error(JsonErrorKind::IntegerOutOfRange))?;
+            self.position += length;
+            return Ok(Json::Int(int));
+        }
+
+        self.position += length;
+        text.parse::<f64>().map(Json::Float).map_err(|_| invalid)
+    }
+}
+
+impl FromStr for Json {
+    type Err = JsonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = JsonParser {
+            input: s,
+            position: 0,
+        };
+        let value = parser.parse_value()?;
+
+        parser.skip_whitespace();
+        if parser.position < s.len() {
+            return Err(parser.error(JsonErrorKind::TrailingCharacters));
+        }
+
+        Ok(value)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for chr in string.chars() {
+        match chr {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            chr if (chr as u32) < 0x20 => write!(f, "\\u{:04x}", chr as u32)?,
+            chr => write!(f, "{chr}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Compact JSON, without any whitespace.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(bool) => write!(f, "{bool}"),
+            Self::Int(int) => write!(f, "{int}"),
+            // JSON can't hold NaN or infinities
+            Self::Float(float) if !float.is_finite() => write!(f, "null"),
+            // a float always keeps a `.` or an exponent, so it can't come
+            // back as an integer
+            Self::Float(float) if float.fract() == 0.0 && float.abs() < 1e16 => {
+                write!(f, "{float:.1}")
+            }
+            Self::Float(float) if float.fract() == 0.0 => write!(f, "{float:e}"),
+            Self::Float(float) => write!(f, "{float}"),
+            Self::String(string) => write_string(f, string),
+            Self::Array(array) => {
+                write!(f, "[")?;
+                for (idx, value) in array.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(object) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in object.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = r#"{"a":[1,-2,3.5,1e3,true,false,null],"b\n\"c\"":{},"d":[]}"#;
+        let json = text.parse::<Json>().unwrap();
+
+        assert_eq!(
+            json.to_string(),
+            r#"{"a":[1,-2,3.5,1000.0,true,false,null],"b\n\"c\"":{},"d":[]}"#
+        );
+        assert_eq!(json.to_string().parse::<Json>(), Ok(json));
+
+        assert_eq!(
+            " [ \"\\u00e9\\ud83d\\ude00\\/\" ] ".parse::<Json>(),
+            Ok(Json::Array(vec![Json::String("é😀/".to_string())]))
+        );
+        assert_eq!(
+            "[99999999999999999999.0, 0.5e+1, 1E-2, -0]".parse::<Json>(),
+            Ok(Json::Array(vec![
+                Json::Float(1e20),
+                Json::Float(5.0),
+                Json::Float(0.01),
+                Json::Int(0)
+            ]))
+        );
+
+        let floats = Json::Array(
+            [1e20, 1e17, 1.5e300, -2e16, 1e-7, 0.0]
+                .into_iter()
+                .map(Json::Float)
+                .collect(),
+        );
+        assert_eq!(
+            floats.to_string(),
+            "[1e20,1e17,1.5e300,-2e16,0.0000001,0.0]"
+        );
+        assert_eq!(floats.to_string().parse::<Json>(), Ok(floats));
+    }
+
+    #[test]
+    fn errors() {
+        let errors = [
+            ("[1,2", 4, JsonErrorKind::UnexpectedEnd),
+            ("[1 2]", 3, JsonErrorKind::UnexpectedChar('2')),
+            ("{\"a\" 1}", 5, JsonErrorKind::UnexpectedChar('1')),
+            ("[01]", 1, JsonErrorKind::InvalidNumber),
+            ("[1.]", 1, JsonErrorKind::InvalidNumber),
+            ("[1.e5]", 1, JsonErrorKind::InvalidNumber),
+            ("[1e+]", 1, JsonErrorKind::InvalidNumber),
+            ("[-.5]", 1, JsonErrorKind::InvalidNumber),
+            ("[1.5.2]", 1, JsonErrorKind::InvalidNumber),
+            ("[1-2]", 1, JsonErrorKind::InvalidNumber),
+            (
+                "[99999999999999999999]",
+                1,
+                JsonErrorKind::IntegerOutOfRange,
+            ),
+            ("\"\\x\"", 2, JsonErrorKind::InvalidEscape),
+            ("nul", 0, JsonErrorKind::UnexpectedChar('n')),
+            ("[] []", 3, JsonErrorKind::TrailingCharacters),
+        ];
+
+        for (input, position, kind) in errors {
+            assert_eq!(
+                input.parse::<Json>(),
+                Err(JsonError { position, kind }),
+                "{input}"
+            );
+        }
+    }
+}
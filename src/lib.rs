@@ -11,3 +11,10 @@ mod day10;
 mod day11;
 mod day12;
 mod day13;
+mod image;
+mod interval;
+mod iter;
+mod json;
+mod ocr;
+#[cfg(test)]
+mod rng;
@@ -0,0 +1,158 @@
+use std::fmt;
+
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+
+/// The capital letters the puzzles draw, each 4 pixels wide and 6 tall,
+/// with one blank column between letters.
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+    (' ', ["....", "....", "....", "....", "....", "...."]),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownGlyph {
+    /// Position of the letter in the text, starting at 0.
+    pub index: usize,
+    pub rows: [String; GLYPH_HEIGHT],
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OcrError {
+    InvalidHeight(usize),
+    UnknownGlyphs(Vec<UnknownGlyph>),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeight(height) => {
+                write!(f, "expected {GLYPH_HEIGHT} rows of pixels, got {height}")
+            }
+            Self::UnknownGlyphs(glyphs) => {
+                write!(f, "{} unknown glyph(s)", glyphs.len())?;
+                for glyph in glyphs {
+                    write!(f, "\n\nletter {}:", glyph.index)?;
+                    for row in glyph.rows.iter() {
+                        write!(f, "\n{row}")?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reads the letters off a screen of `#` (lit) and `.` or space (dark)
+/// pixels. Short rows are treated as dark on the right, so the last column
+/// may be missing.
+#[allow(dead_code)]
+pub fn recognise(screen: &str) -> Result<String, OcrError> {
+    let rows = screen
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().map(|chr| chr == '#').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    if rows.len() != GLYPH_HEIGHT {
+        return Err(OcrError::InvalidHeight(rows.len()));
+    }
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let letters = width.div_ceil(GLYPH_WIDTH + 1);
+
+    let mut result = String::new();
+    let mut unknown = vec![];
+
+    for index in 0..letters {
+        let glyph = std::array::from_fn::<_, GLYPH_HEIGHT, _>(|y| {
+            (0..GLYPH_WIDTH)
+                .map(|x| {
+                    rows[y]
+                        .get(index * (GLYPH_WIDTH + 1) + x)
+                        .copied()
+                        .unwrap_or(false)
+                })
+                .map(|lit| if lit { '#' } else { '.' })
+                .collect::<String>()
+        });
+
+        match GLYPHS.iter().find(|(_, pattern)| *pattern == glyph) {
+            Some((letter, _)) => result.push(*letter),
+            None => unknown.push(UnknownGlyph { index, rows: glyph }),
+        }
+    }
+
+    if unknown.is_empty() {
+        Ok(result.trim_end().to_string())
+    } else {
+        Err(OcrError::UnknownGlyphs(unknown))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn render(text: &str) -> String {
+        (0..GLYPH_HEIGHT)
+            .map(|y| {
+                text.chars()
+                    .map(|chr| GLYPHS.iter().find(|(letter, _)| *letter == chr).unwrap().1[y])
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn alphabet() {
+        let alphabet = GLYPHS.iter().map(|(letter, _)| *letter).collect::<String>();
+
+        assert_eq!(
+            recognise(&render(&alphabet)),
+            Ok(alphabet.trim_end().to_string())
+        );
+        assert_eq!(recognise(&render("EHPZPJGL")), Ok("EHPZPJGL".to_string()));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(recognise("####\n#..."), Err(OcrError::InvalidHeight(2)));
+
+        let mut screen = render("AB")
+            .lines()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>();
+        screen[0].replace_range(5..9, "####");
+
+        let error = recognise(&screen.join("\n")).unwrap_err();
+        assert_eq!(
+            error,
+            OcrError::UnknownGlyphs(vec![UnknownGlyph {
+                index: 1,
+                rows: ["####", "#..#", "###.", "#..#", "#..#", "###."].map(String::from),
+            }])
+        );
+        assert!(error
+            .to_string()
+            .starts_with("1 unknown glyph(s)\n\nletter 1:\n####"));
+    }
+}